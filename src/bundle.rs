@@ -0,0 +1,186 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::checker::Checker;
+use crate::data::Record;
+use crate::{store, App, Error, Result};
+
+/// Bumped when a change to the format can't be read by older versions
+const VERSION: u32 = 1;
+
+/// Everything needed to set up the same apps on another machine, written by `ups export`
+///
+/// Script paths and working directories under the home directory are written as `~/..`,
+/// embedded scripts as `scripts/<sha256>` keys of `scripts`, so apps with different scripts
+/// never share a key
+#[derive(Serialize, Deserialize)]
+pub struct Bundle {
+    version: u32,
+    apps: BTreeMap<String, Record>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    scripts: BTreeMap<String, String>,
+}
+
+/// What `ups import` does with an app that is already registered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Conflict {
+    /// Keep ours
+    #[default]
+    Skip,
+    /// Take theirs
+    Overwrite,
+    /// Keep ours, but take their snapshot if it is newer
+    KeepNewer,
+}
+
+impl Conflict {
+    pub const NAMES: &'static [&'static str] = &["skip", "overwrite", "keep-newer"];
+}
+
+impl FromStr for Conflict {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            "keep-newer" => Ok(Self::KeepNewer),
+            _ => Err(format!(
+                "Unknown conflict mode `{}`, expected one of: {}",
+                s,
+                Self::NAMES.join(", ")
+            )
+            .into()),
+        }
+    }
+}
+
+impl Bundle {
+    pub fn new(apps: &HashMap<String, App>, embed_scripts: bool) -> Result<Self> {
+        let home = dirs::home_dir();
+        let mut bundle = Self {
+            version: VERSION,
+            apps: BTreeMap::new(),
+            scripts: BTreeMap::new(),
+        };
+        for (name, app) in apps {
+            let mut app = app.clone();
+            // Wherever it comes from here, it's a plain app over there
+            app.from_config = false;
+            for checker in scripts(&mut app) {
+                let Checker::Script(path) = checker else {
+                    continue;
+                };
+                *path = if embed_scripts {
+                    let content = std::fs::read_to_string(&*path)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                    let key = format!("scripts/{}", store::hash(content.as_bytes()));
+                    bundle.scripts.insert(key.clone(), content);
+                    key.into()
+                } else {
+                    under_tilde(path, home.as_deref())
+                };
+            }
+            if let Some(cwd) = &mut app.exec.cwd {
+                *cwd = under_tilde(cwd, home.as_deref());
+            }
+            bundle.apps.insert(name.clone(), Record::new(&app));
+        }
+        Ok(bundle)
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        let bundle: Self =
+            serde_json::from_str(s).map_err(|e| format!("Error while parsing bundle: {}", e))?;
+        if bundle.version > VERSION {
+            return Err(format!(
+                "The bundle was written by a newer version of ups (format {}, this one reads up to {})",
+                bundle.version, VERSION
+            )
+            .into());
+        }
+        Ok(bundle)
+    }
+
+    /// The apps as written in the bundle, `install` makes their scripts usable here
+    pub fn apps(&self) -> Result<Vec<(String, App)>> {
+        self.apps
+            .iter()
+            .map(|(name, record)| Ok((name.clone(), record.clone().into_app()?)))
+            .collect()
+    }
+
+    /// Points the app's scripts to this machine, embedded ones are added to the store
+    pub fn install(&self, app: &mut App) -> Result<()> {
+        let home = dirs::home_dir();
+        for checker in scripts(app) {
+            let Checker::Script(path) = checker else {
+                continue;
+            };
+            let key = path.to_string_lossy().into_owned();
+            if let Some(content) = self.scripts.get(&key) {
+                *path = store::add(content.as_bytes(), None)?;
+            } else {
+                *path = expand_tilde(path, home.as_deref());
+            }
+        }
+        if let Some(cwd) = &mut app.exec.cwd {
+            *cwd = expand_tilde(cwd, home.as_deref());
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Bundle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let json = serde_json::to_string_pretty(self).map_err(|_| std::fmt::Error)?;
+        writeln!(f, "{}", json)
+    }
+}
+
+/// `path` as `~/..` when it is under the home directory, so it works for another user
+fn under_tilde(path: &Path, home: Option<&Path>) -> PathBuf {
+    match home.and_then(|home| path.strip_prefix(home).ok()) {
+        Some(relative) => Path::new("~").join(relative),
+        None => path.to_owned(),
+    }
+}
+
+fn expand_tilde(path: &Path, home: Option<&Path>) -> PathBuf {
+    match (path.strip_prefix("~"), home) {
+        (Ok(relative), Some(home)) => home.join(relative),
+        _ => path.to_owned(),
+    }
+}
+
+/// The checker and packaged source
+fn scripts(app: &mut App) -> Vec<&mut Checker> {
+    std::iter::once(&mut app.checker)
+        .chain(&mut app.packaged)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tilde_round_trip() {
+        let home = Path::new("/home/me");
+        let exported = under_tilde(Path::new("/home/me/ups/check.sh"), Some(home));
+        assert_eq!(exported, Path::new("~/ups/check.sh"));
+        let other = Path::new("/home/you");
+        assert_eq!(
+            expand_tilde(&exported, Some(other)),
+            Path::new("/home/you/ups/check.sh")
+        );
+        assert_eq!(
+            under_tilde(Path::new("/opt/ups"), Some(home)),
+            Path::new("/opt/ups")
+        );
+        assert_eq!(expand_tilde(&exported, None), exported);
+    }
+}
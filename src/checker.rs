@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::time::Instant;
+
+use crate::outcome::{Failure, FailureKind};
+use crate::provider::{Latest, Provider, Spec, Step};
+use crate::{process, Error, Result, TimedOut, Unavailable};
+
+/// How the latest value of an app is found
+#[derive(Debug, Clone)]
+pub enum Checker {
+    /// A script whose trimmed stdout is the latest value
+    Script(PathBuf),
+    /// Same as a script, but a shell command line
+    Command(String),
+    Provider(Provider),
+}
+
+/// How a script or command is run, so one script can serve several apps
+#[derive(Debug, Clone, Default)]
+pub struct Exec {
+    /// Given to a script as is, to a command as `$1`, `$2`..
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub cwd: Option<PathBuf>,
+}
+
+impl Exec {
+    fn command(&self, mut command: Command) -> Command {
+        command.args(&self.args).envs(&self.env);
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        command
+    }
+}
+
+/// Exit code of a script telling that nothing changed since the previous check, it can
+/// then skip the work of finding the value again
+pub const UNCHANGED_EXIT_CODE: i32 = 100;
+
+impl Checker {
+    /// How a command is told apart from a script path when written as a string
+    const COMMAND_PREFIX: &'static str = "cmd:";
+
+    /// Parses the arguments of `ups insert [app] ..`, either a single script path or provider
+    /// spec, `--cmd <command>`, or `--<provider> <target>` followed by `--<option> <value>` pairs
+    pub fn from_args(args: &[&str]) -> Result<Self> {
+        match args {
+            [arg] if !arg.starts_with("--") => Self::from_arg(arg),
+            ["--cmd", command] => Ok(Self::Command((*command).to_owned())),
+            _ => {
+                let mut spec: Option<Spec> = None;
+                let mut options = vec![];
+                let mut args = args.iter();
+                while let Some(flag) = args.next() {
+                    let key = flag
+                        .strip_prefix("--")
+                        .ok_or_else(|| format!("Expected a flag, got `{}`", flag))?;
+                    let value = if Step::FLAGS.contains(&key) {
+                        ""
+                    } else {
+                        args.next()
+                            .ok_or_else(|| format!("Missing value for `{}`", flag))?
+                    };
+                    if Provider::is_kind(key) {
+                        if spec.is_some() {
+                            return Err("Only one provider can be specified".into());
+                        }
+                        spec = Some(Spec::new(key, value));
+                    } else {
+                        options.push((key.to_owned(), value.to_owned()));
+                    }
+                }
+                let mut spec = spec.ok_or_else(|| {
+                    format!(
+                        "Expected a script path or one of: {}",
+                        Provider::KINDS
+                            .iter()
+                            .map(|kind| format!("--{}", kind))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })?;
+                spec.options = options;
+                Ok(Self::Provider(Provider::from_spec(spec)?))
+            }
+        }
+    }
+
+    /// A provider spec like `aur:mold`, or else a script path
+    pub fn from_arg(arg: &str) -> Result<Self> {
+        match arg.parse()? {
+            Self::Script(path) => Ok(Self::Script(path.canonicalize()?)),
+            provider => Ok(provider),
+        }
+    }
+
+    pub fn script_path(&self) -> Option<&Path> {
+        match self {
+            Self::Script(path) => Some(path),
+            Self::Command(_) | Self::Provider(_) => None,
+        }
+    }
+
+    /// Gives up at `deadline`, `exec` only matters to scripts and commands
+    pub fn fetch_latest(
+        &self,
+        exec: &Exec,
+        deadline: Instant,
+    ) -> std::result::Result<Latest, Failure> {
+        match self {
+            Self::Script(_) | Self::Command(_) => {
+                let mut command = exec.command(match self {
+                    Self::Command(command) => {
+                        let mut sh = Command::new("sh");
+                        // `$0`, the arguments follow as `$1`..
+                        sh.arg("-c").arg(command).arg("sh");
+                        sh
+                    }
+                    _ => Command::new(self.script_path().expect("Is a script")),
+                });
+                let output = match process::output(&mut command, deadline) {
+                    Ok(output) => output,
+                    Err(e) if e.is::<TimedOut>() => return Err(Failure::new(FailureKind::Timeout)),
+                    Err(e) => {
+                        return Err(
+                            Failure::new(FailureKind::Spawn).stderr(e.to_string().as_bytes())
+                        )
+                    }
+                };
+                if output.status.code() == Some(UNCHANGED_EXIT_CODE) {
+                    return Ok(Latest {
+                        unchanged: true,
+                        ..Latest::default()
+                    });
+                }
+                if !output.status.success() {
+                    return Err(Failure {
+                        exit_code: output.status.code(),
+                        ..Failure::new(FailureKind::Exit).stderr(&output.stderr)
+                    });
+                }
+                let Ok(value) = String::from_utf8(output.stdout) else {
+                    return Err(Failure::new(FailureKind::Encoding).stderr(&output.stderr));
+                };
+                match value.trim() {
+                    "" => Err(Failure::new(FailureKind::Empty).stderr(&output.stderr)),
+                    value => Ok(value.to_owned().into()),
+                }
+            }
+            Self::Provider(provider) => match provider.fetch_latest(deadline) {
+                Err(e) if e.is::<TimedOut>() => Err(Failure::new(FailureKind::Timeout)),
+                Err(e) if e.is::<Unavailable>() => {
+                    Err(Failure::new(FailureKind::Unavailable).stderr(e.to_string().as_bytes()))
+                }
+                Err(e) => Err(Failure::new(FailureKind::Provider).stderr(e.to_string().as_bytes())),
+                Ok(latest) => Ok(latest),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Checker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Script(path) => path.display().fmt(f),
+            Self::Command(command) => write!(f, "{}{}", Self::COMMAND_PREFIX, command),
+            Self::Provider(provider) => provider.fmt(f),
+        }
+    }
+}
+
+impl FromStr for Checker {
+    type Err = Error;
+
+    /// Reads back what `Display` wrote, anything that isn't a provider spec is a script path
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(command) = s.strip_prefix(Self::COMMAND_PREFIX) {
+            return Ok(Self::Command(command.to_owned()));
+        }
+        match s.split_once(':') {
+            Some((kind, _)) if Provider::is_kind(kind) => Ok(Self::Provider(s.parse()?)),
+            _ => Ok(Self::Script(s.into())),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::checker::Checker;
+use crate::options::Options;
+use crate::Result;
+
+/// `apps.toml`, the apps a team keeps in version control
+///
+/// ```toml
+/// [apps.mold]
+/// provider = "github:rui314/mold"
+/// scheme = "semver"
+/// normalize = ["strip-prefix:v"]
+///
+/// [apps.foo]
+/// script = "scripts/foo.sh" # relative to this file
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    #[serde(default)]
+    apps: BTreeMap<String, AppConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AppConfig {
+    /// A provider spec, like `github:rui314/mold`
+    provider: Option<String>,
+    script: Option<String>,
+    /// Run with `sh -c`
+    command: Option<String>,
+    packaged: Option<String>,
+    scheme: Option<String>,
+    #[serde(default)]
+    normalize: Vec<String>,
+    /// Seconds
+    timeout: Option<f64>,
+    retries: Option<u32>,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    /// Relative to this file
+    cwd: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// What loading the config changed in the registry, reported by `ups sync`
+#[derive(Debug, Default)]
+pub struct Sync {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// The declared apps, with every option set so the config fully decides them
+pub fn read(path: &Path) -> Result<BTreeMap<String, (Checker, Options)>> {
+    let config: Config = toml::from_str(&std::fs::read_to_string(path)?)
+        .map_err(|e| format!("Error while parsing {}: {}", path.display(), e))?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    config
+        .apps
+        .into_iter()
+        .map(|(name, app)| {
+            let declared = app
+                .declare(dir)
+                .map_err(|e| format!("{}: app `{}`: {}", path.display(), name, e))?;
+            Ok((name, declared))
+        })
+        .collect()
+}
+
+impl AppConfig {
+    fn declare(self, dir: &Path) -> Result<(Checker, Options)> {
+        let checker = match (self.provider, self.script, self.command) {
+            (Some(spec), None, None) => Checker::Provider(spec.parse()?),
+            (None, Some(script), None) => Checker::Script(dir.join(script)),
+            (None, None, Some(command)) => Checker::Command(command),
+            _ => return Err("Expected one of `provider`, `script` or `command`".into()),
+        };
+        let options = Options {
+            packaged: Some(match self.packaged {
+                Some(packaged) => match packaged.parse()? {
+                    Checker::Script(path) => Some(Checker::Script(dir.join(path))),
+                    packaged => Some(packaged),
+                },
+                None => None,
+            }),
+            scheme: Some(
+                self.scheme
+                    .map(|s| s.parse())
+                    .transpose()?
+                    .unwrap_or_default(),
+            ),
+            normalize: Some(
+                self.normalize
+                    .iter()
+                    .map(|rule| rule.parse())
+                    .collect::<Result<_>>()?,
+            ),
+            timeout: Some(match self.timeout {
+                Some(timeout) => Some(
+                    Duration::try_from_secs_f64(timeout)
+                        .map_err(|_| format!("Expected a number of seconds, got `{}`", timeout))?,
+                ),
+                None => None,
+            }),
+            retries: Some(self.retries),
+            store: false,
+            args: Some(self.args),
+            env: Some(self.env),
+            cwd: Some(self.cwd.map(|cwd| dir.join(cwd))),
+            tags: Some(
+                self.tags
+                    .iter()
+                    .map(|tag| crate::select::parse_tags(tag))
+                    .collect::<Result<Vec<_>>>()?
+                    .concat(),
+            ),
+        };
+        Ok((checker, options))
+    }
+}
@@ -0,0 +1,569 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::checker::Exec;
+use crate::outcome::Failure;
+use crate::provider::{decode, RepoVersion};
+use crate::version::Scheme;
+use crate::{options, App, Result, NONE};
+
+/// Bumped when a change to the format can't be read by older versions
+pub const VERSION: u32 = 1;
+
+/// Name of the tab separated file used before the format was versioned
+pub const LEGACY_FILE: &str = "data";
+
+#[derive(Serialize, Deserialize)]
+struct Data {
+    version: u32,
+    #[serde(default)]
+    apps: BTreeMap<String, Record>,
+}
+
+/// How an app is stored, settings are kept in the same string forms the CLI shows
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    checker: String,
+    snapshot_value: String,
+    latest_value: String,
+    raw_latest_value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    packaged: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    packaged_value: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    repos: Vec<RepoVersion>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scheme: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    normalize: Vec<String>,
+    /// Seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retries: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    failure: Option<FailureRecord>,
+    /// Seconds the last check took
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duration: Option<f64>,
+    /// When the last check ran, in seconds since the epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checked: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    from_config: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    args: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cwd: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+struct FailureRecord {
+    kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    stderr: String,
+}
+
+impl Record {
+    pub fn new(app: &App) -> Self {
+        Self {
+            checker: app.checker.to_string(),
+            snapshot_value: app.snapshot_value.clone(),
+            latest_value: app.latest_value.clone(),
+            raw_latest_value: app.raw_latest_value.clone(),
+            packaged: app.packaged.as_ref().map(ToString::to_string),
+            packaged_value: app.packaged.as_ref().map(|_| app.packaged_value.clone()),
+            repos: app.repos.clone(),
+            scheme: (app.scheme != Scheme::default()).then(|| app.scheme.to_string()),
+            normalize: app.normalize.iter().map(ToString::to_string).collect(),
+            timeout: app.timeout.map(|timeout| timeout.as_secs_f64()),
+            retries: app.retries,
+            failure: app.failure.as_ref().map(|failure| FailureRecord {
+                kind: failure.kind.to_string(),
+                exit_code: failure.exit_code,
+                stderr: failure.stderr.clone(),
+            }),
+            duration: app.duration.map(|duration| duration.as_secs_f64()),
+            checked: app
+                .checked
+                .and_then(|checked| Some(checked.duration_since(UNIX_EPOCH).ok()?.as_secs())),
+            from_config: app.from_config,
+            args: app.exec.args.clone(),
+            env: app.exec.env.clone(),
+            cwd: app.exec.cwd.clone(),
+            tags: app.tags.clone(),
+        }
+    }
+
+    pub fn into_app(self) -> Result<App> {
+        let mut app = App::new(self.checker.parse()?);
+        app.snapshot_value = self.snapshot_value;
+        app.latest_value = self.latest_value;
+        app.raw_latest_value = self.raw_latest_value;
+        app.packaged = self.packaged.map(|packaged| packaged.parse()).transpose()?;
+        app.packaged_value = self.packaged_value.unwrap_or_else(|| NONE.to_owned());
+        app.repos = self.repos;
+        app.scheme = self
+            .scheme
+            .map(|scheme| scheme.parse())
+            .transpose()?
+            .unwrap_or_default();
+        app.normalize = self
+            .normalize
+            .iter()
+            .map(|rule| rule.parse())
+            .collect::<Result<_>>()?;
+        app.timeout = self.timeout.map(seconds).transpose()?;
+        app.retries = self.retries;
+        app.failure = match self.failure {
+            Some(failure) => Some(Failure {
+                exit_code: failure.exit_code,
+                stderr: failure.stderr,
+                ..Failure::new(failure.kind.parse()?)
+            }),
+            None => None,
+        };
+        app.duration = self.duration.map(seconds).transpose()?;
+        app.checked = self
+            .checked
+            .map(|checked| UNIX_EPOCH + Duration::from_secs(checked));
+        app.from_config = self.from_config;
+        app.exec = Exec {
+            args: self.args,
+            env: self.env,
+            cwd: self.cwd,
+        };
+        app.tags = self.tags;
+        Ok(app)
+    }
+}
+
+fn seconds(seconds: f64) -> Result<Duration> {
+    Ok(Duration::try_from_secs_f64(seconds)?)
+}
+
+/// The apps as they were read, so a save only writes what the command changed
+#[derive(Default)]
+pub struct Base(BTreeMap<String, Record>);
+
+pub fn read(path: &Path) -> Result<(HashMap<String, App>, Base)> {
+    let data = read_data(path)?;
+    let apps = data
+        .apps
+        .iter()
+        .map(|(name, record)| Ok((name.clone(), record.clone().into_app()?)))
+        .collect::<Result<_>>()?;
+    Ok((apps, Base(data.apps)))
+}
+
+/// Applies the fields that differ from `base` to what is on disk now, another ups may have
+/// saved since we read it and its changes, to other apps or to other fields of the same
+/// app, must survive
+///
+/// The lock is only held here rather than from `read` on, a check can take minutes and
+/// would block every other ups meanwhile, merging fields keeps both sides' changes instead
+pub fn save(path: &Path, apps: &HashMap<String, App>, base: &Base) -> Result<()> {
+    let mut changes = vec![];
+    for (name, app) in apps {
+        let record = Record::new(app);
+        match base.0.get(name) {
+            Some(base) if *base == record => {}
+            Some(base) => {
+                let fields = changed_fields(base, &record)?;
+                changes.push((name, Change::Patch(fields)));
+            }
+            None => changes.push((name, Change::Insert(Box::new(record)))),
+        }
+    }
+    for name in base.0.keys().filter(|name| !apps.contains_key(*name)) {
+        changes.push((name, Change::Remove));
+    }
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let _lock = Lock::acquire(path)?;
+    let mut data = match path.exists() {
+        true => read_data(path)?,
+        false => Data {
+            version: VERSION,
+            apps: BTreeMap::new(),
+        },
+    };
+    for (name, change) in changes {
+        let record = match (change, data.apps.remove(name)) {
+            (Change::Remove, _) => continue,
+            (Change::Patch(fields), Some(theirs)) => patch(theirs, fields)?,
+            // Removed by the other ups while we changed it, the removal wins
+            (Change::Patch(_), None) => continue,
+            (Change::Insert(record), _) => *record,
+        };
+        data.apps.insert(name.clone(), record);
+    }
+    data.version = VERSION;
+    write(path, &data)
+}
+
+/// What a save does to an app on disk
+enum Change {
+    Insert(Box<Record>),
+    /// Sets these fields, dropped if the app is no longer there
+    Patch(Vec<(String, Option<Value>)>),
+    Remove,
+}
+
+/// The fields of `record` that differ from `base`, `None` for the ones it leaves out
+fn changed_fields(base: &Record, record: &Record) -> Result<Vec<(String, Option<Value>)>> {
+    let (Value::Object(base), Value::Object(record)) =
+        (serde_json::to_value(base)?, serde_json::to_value(record)?)
+    else {
+        unreachable!("A record is a struct");
+    };
+    let keys: BTreeSet<&String> = base.keys().chain(record.keys()).collect();
+    Ok(keys
+        .into_iter()
+        .filter(|key| base.get(*key) != record.get(*key))
+        .map(|key| (key.clone(), record.get(key).cloned()))
+        .collect())
+}
+
+fn patch(record: Record, fields: Vec<(String, Option<Value>)>) -> Result<Record> {
+    let Value::Object(mut record) = serde_json::to_value(record)? else {
+        unreachable!("A record is a struct");
+    };
+    for (key, value) in fields {
+        match value {
+            Some(value) => record.insert(key, value),
+            None => record.remove(&key),
+        };
+    }
+    Ok(serde_json::from_value(Value::Object(record))?)
+}
+
+fn read_data(path: &Path) -> Result<Data> {
+    let data: Data = serde_json::from_str(&std::fs::read_to_string(path)?)
+        .map_err(|e| format!("Error while parsing {}: {}", path.display(), e))?;
+    if data.version > VERSION {
+        return Err(format!(
+            "{} was written by a newer version of ups (format {}, this one reads up to {})",
+            path.display(),
+            data.version,
+            VERSION
+        )
+        .into());
+    }
+    Ok(data)
+}
+
+fn write(path: &Path, data: &Data) -> Result<()> {
+    write_atomic(
+        path,
+        (serde_json::to_string_pretty(data)? + "\n").as_bytes(),
+    )
+}
+
+/// Writes a temporary file next to `path` and renames it over, so a crash leaves either
+/// the old or the new file, never half of one
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+    let result = (|| -> Result<()> {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result?;
+    // The rename itself only lasts once the directory is synced
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Advisory lock on a file next to the data, held while a save reads, merges and writes
+pub struct Lock(File);
+
+impl Lock {
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let file = File::create(path.with_extension("lock"))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            // SAFETY: plain syscall on a descriptor we own, released when it is closed
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+        }
+        Ok(Self(file))
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            // SAFETY: same descriptor as in `acquire`
+            unsafe { libc::flock(self.0.as_raw_fd(), libc::LOCK_UN) };
+        }
+    }
+}
+
+/// Converts the legacy file at `legacy` to `path`, the legacy file is kept next to it
+/// with a `.bak` extension
+pub fn migrate(legacy: &Path, path: &Path) -> Result<()> {
+    let _lock = Lock::acquire(path)?;
+    // Another ups may have migrated while we waited
+    if path.exists() {
+        return Ok(());
+    }
+    let apps = read_legacy(legacy)?;
+    write(
+        path,
+        &Data {
+            version: VERSION,
+            apps: apps
+                .iter()
+                .map(|(name, app)| (name.clone(), Record::new(app)))
+                .collect(),
+        },
+    )?;
+    let backup = legacy.with_extension("bak");
+    std::fs::rename(legacy, &backup)?;
+    eprintln!(
+        "Migrated {} to {}, the old file is kept as {}",
+        legacy.display(),
+        path.display(),
+        backup.display()
+    );
+    Ok(())
+}
+
+/// Lines of `name\tsnapshot\tlatest\tchecker\t` followed by optional `key=value\t` columns
+fn read_legacy(path: &Path) -> Result<HashMap<String, App>> {
+    const PARSE_ERROR: &str = "Error while parsing data file";
+    let data = std::fs::read_to_string(path)?;
+
+    let mut apps = HashMap::new();
+    for line in data.lines() {
+        let mut line = line.split('\t').filter(|field| !field.is_empty());
+        let name = line.next().ok_or(PARSE_ERROR)?;
+        let snapshot_value = line.next().ok_or(PARSE_ERROR)?;
+        let latest_value = line.next().ok_or(PARSE_ERROR)?;
+        let checker = line.next().ok_or(PARSE_ERROR)?;
+        let mut app = App::new(checker.parse()?);
+        app.latest_value = latest_value.into();
+        app.raw_latest_value = latest_value.into();
+        app.snapshot_value = snapshot_value.into();
+        // The failure fields come together, `error` first
+        let mut failure: Option<Failure> = None;
+        for field in line {
+            let (key, value) = field.split_once('=').ok_or(PARSE_ERROR)?;
+            let value = decode(value)?;
+            match key {
+                "packaged" => app.packaged = Some(value.parse()?),
+                "packaged_value" => app.packaged_value = value,
+                "repo" => app.repos.push(value.parse()?),
+                "scheme" => app.scheme = value.parse()?,
+                "normalize" => app.normalize.push(value.parse()?),
+                "raw_latest_value" => app.raw_latest_value = value,
+                "timeout" => app.timeout = Some(options::parse_seconds(&value)?),
+                "retries" => app.retries = Some(options::parse_retries(&value)?),
+                "error" => failure = Some(Failure::new(value.parse()?)),
+                "exit_code" => {
+                    failure.as_mut().ok_or(PARSE_ERROR)?.exit_code = Some(value.parse()?)
+                }
+                "stderr" => failure.as_mut().ok_or(PARSE_ERROR)?.stderr = value,
+                "duration" => app.duration = Some(options::parse_seconds(&value)?),
+                _ => return Err(format!("{}: unknown field `{}`", PARSE_ERROR, key).into()),
+            }
+        }
+        app.failure = failure;
+        apps.insert(name.into(), app);
+    }
+    Ok(apps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ups-data-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn app(latest: &str) -> App {
+        let mut app = App::new("github:owner/repo".parse().unwrap());
+        app.latest_value = latest.to_owned();
+        app
+    }
+
+    #[test]
+    fn patch_does_not_revive_a_removed_app() {
+        let dir = scratch("remove");
+        let path = dir.join("data.json");
+        let apps = HashMap::from([
+            ("foo".to_owned(), app("1.0")),
+            ("bar".to_owned(), app("1.0")),
+        ]);
+        save(&path, &apps, &Base::default()).unwrap();
+
+        // `ups remove foo` and a check of everything, both started from the same file
+        let (mut removing, removing_base) = read(&path).unwrap();
+        let (mut checking, checking_base) = read(&path).unwrap();
+        removing.remove("foo");
+        save(&path, &removing, &removing_base).unwrap();
+        for app in checking.values_mut() {
+            app.latest_value = "2.0".to_owned();
+            app.duration = Some(Duration::from_secs(1));
+        }
+        save(&path, &checking, &checking_base).unwrap();
+
+        let (apps, _) = read(&path).unwrap();
+        assert_eq!(apps.keys().collect::<Vec<_>>(), ["bar"]);
+        assert_eq!(apps["bar"].latest_value, "2.0");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn both_sides_keep_their_fields() {
+        let dir = scratch("fields");
+        let path = dir.join("data.json");
+        let apps = HashMap::from([("foo".to_owned(), app("1.0"))]);
+        save(&path, &apps, &Base::default()).unwrap();
+
+        let (mut snapshotting, snapshotting_base) = read(&path).unwrap();
+        let (mut checking, checking_base) = read(&path).unwrap();
+        snapshotting.get_mut("foo").unwrap().snapshot_value = "1.0".to_owned();
+        checking.get_mut("foo").unwrap().latest_value = "2.0".to_owned();
+        checking.insert("new".to_owned(), app("0.1"));
+        save(&path, &snapshotting, &snapshotting_base).unwrap();
+        save(&path, &checking, &checking_base).unwrap();
+
+        let (apps, _) = read(&path).unwrap();
+        assert_eq!(apps["foo"].snapshot_value, "1.0");
+        assert_eq!(apps["foo"].latest_value, "2.0");
+        assert_eq!(apps["new"].latest_value, "0.1");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn migrate_baseline() {
+        let dir = scratch("baseline");
+        let (legacy, path) = (dir.join(LEGACY_FILE), dir.join("data.json"));
+        std::fs::write(
+            &legacy,
+            "loguru\t0.7.1\t0.7.2\t/home/me/my scripts/loguru.sh\t\nmold\tNONE\t2.0\t/bin/mold.sh\t\n",
+        )
+        .unwrap();
+        migrate(&legacy, &path).unwrap();
+
+        let (apps, _) = read(&path).unwrap();
+        assert_eq!(apps.len(), 2);
+        let loguru = &apps["loguru"];
+        assert_eq!(
+            loguru.checker.script_path(),
+            Some(Path::new("/home/me/my scripts/loguru.sh"))
+        );
+        assert_eq!(loguru.snapshot_value, "0.7.1");
+        assert_eq!(loguru.latest_value, "0.7.2");
+        assert_eq!(loguru.raw_latest_value, "0.7.2");
+        assert_eq!(apps["mold"].snapshot_value, NONE);
+
+        // The legacy file is kept as is, and a second run leaves everything alone
+        assert!(!legacy.exists());
+        let backup = std::fs::read(dir.join("data.bak")).unwrap();
+        assert!(backup.starts_with(b"loguru\t0.7.1\t"));
+        let migrated = std::fs::read(&path).unwrap();
+        std::fs::write(&legacy, "other\t1\t1\t/bin/other.sh\t\n").unwrap();
+        migrate(&legacy, &path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), migrated);
+        assert_eq!(std::fs::read(dir.join("data.bak")).unwrap(), backup);
+        assert!(legacy.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn migrate_with_fields() {
+        let dir = scratch("legacy-fields");
+        let (legacy, path) = (dir.join(LEGACY_FILE), dir.join("data.json"));
+        let line = [
+            "foo",
+            "1.0",
+            "1.1",
+            "pypi:foo",
+            "packaged=aur:python-foo",
+            "packaged_value=1.0-1",
+            "repo=arch:newest:1.1",
+            "repo=debian_12:outdated:1%3A0.9",
+            "scheme=pep440",
+            "normalize=strip-prefix:v",
+            "normalize=separator:_:.",
+            "raw_latest_value=v1_1",
+            "timeout=2.5",
+            "retries=3",
+            "error=exit",
+            "exit_code=2",
+            "stderr=no%20network",
+            "duration=0.5",
+            "",
+        ];
+        std::fs::write(&legacy, line.join("\t") + "\n").unwrap();
+        migrate(&legacy, &path).unwrap();
+
+        let (apps, _) = read(&path).unwrap();
+        let foo = &apps["foo"];
+        assert_eq!(foo.checker.to_string(), "pypi:foo");
+        assert_eq!(foo.packaged.as_ref().unwrap().to_string(), "aur:python-foo");
+        assert_eq!(foo.packaged_value, "1.0-1");
+        assert_eq!(foo.repos.len(), 2);
+        assert_eq!(foo.repos[1].version, "1:0.9");
+        assert_eq!(foo.scheme, Scheme::Pep440);
+        let rules: Vec<_> = foo.normalize.iter().map(ToString::to_string).collect();
+        assert_eq!(rules, ["strip-prefix:v", "separator:_:."]);
+        assert_eq!(foo.raw_latest_value, "v1_1");
+        assert_eq!(foo.timeout, Some(Duration::from_millis(2500)));
+        assert_eq!(foo.retries, Some(3));
+        let failure = foo.failure.as_ref().unwrap();
+        assert_eq!(failure.kind.to_string(), "exit");
+        assert_eq!(failure.exit_code, Some(2));
+        assert_eq!(failure.stderr, "no network");
+        assert_eq!(foo.duration, Some(Duration::from_millis(500)));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn migrate_refuses_unknown_fields() {
+        let dir = scratch("unknown");
+        let (legacy, path) = (dir.join(LEGACY_FILE), dir.join("data.json"));
+        std::fs::write(&legacy, "foo\t1\t1\t/bin/foo.sh\tcolor=red\t\n").unwrap();
+        let error = migrate(&legacy, &path).unwrap_err();
+        assert!(
+            error.to_string().contains("unknown field `color`"),
+            "{}",
+            error
+        );
+        assert!(legacy.exists() && !path.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::{self, Lock};
+use crate::provider::encode;
+use crate::Result;
+
+/// How long checks are remembered unless `UPS_HISTORY_DAYS` says otherwise, value changes
+/// and snapshots are kept forever
+const DEFAULT_DAYS: u64 = 90;
+
+/// Repeated checks with the same result are merged, unless `UPS_HISTORY_COMPACT=0`
+fn compaction() -> bool {
+    std::env::var("UPS_HISTORY_COMPACT").map_or(true, |compact| compact != "0")
+}
+
+/// One line of an app's history file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Entry {
+    /// Consecutive checks with the same result are compacted into one entry
+    Check {
+        /// Seconds since the epoch, of the first of the checks
+        time: u64,
+        /// Of the last of the checks, when there are several
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        until: Option<u64>,
+        #[serde(default = "one", skip_serializing_if = "is_one")]
+        count: u32,
+        /// `ok`, or the failure as shown in the status column
+        result: String,
+        /// Seconds the last of the checks took
+        duration: f64,
+    },
+    /// A check found a new latest value
+    Change { time: u64, from: String, to: String },
+    /// The snapshot value was changed
+    Snapshot {
+        time: u64,
+        from: String,
+        to: String,
+        /// Set by `ups snapshot --undo`, `to` is then the value it restored
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        undo: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        author: Option<String>,
+    },
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn is_one(count: &u32) -> bool {
+    *count == 1
+}
+
+impl Entry {
+    pub fn check(result: String, duration: Duration) -> Self {
+        Self::Check {
+            time: now(),
+            until: None,
+            count: 1,
+            result,
+            duration: duration.as_secs_f64(),
+        }
+    }
+
+    pub fn change(from: String, to: String) -> Self {
+        Self::Change {
+            time: now(),
+            from,
+            to,
+        }
+    }
+
+    pub fn snapshot(from: String, to: String, undo: bool, note: Option<String>) -> Self {
+        Self::Snapshot {
+            time: now(),
+            from,
+            to,
+            undo,
+            note,
+            author: author(),
+        }
+    }
+
+    /// When the entry was last true
+    fn last_time(&self) -> u64 {
+        match self {
+            Self::Check { time, until, .. } => until.unwrap_or(*time),
+            Self::Change { time, .. } | Self::Snapshot { time, .. } => *time,
+        }
+    }
+
+    /// Folds `next` into `self` if they are checks with the same result
+    fn compact(&mut self, next: &Self) -> bool {
+        match (self, next) {
+            (
+                Self::Check {
+                    until,
+                    count,
+                    result,
+                    duration,
+                    ..
+                },
+                Self::Check {
+                    time: next_time,
+                    result: next_result,
+                    duration: next_duration,
+                    ..
+                },
+            ) if result == next_result => {
+                *until = Some(*next_time);
+                *count += 1;
+                *duration = *next_duration;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Check {
+                time,
+                until,
+                count,
+                result,
+                duration,
+            } => {
+                write!(
+                    f,
+                    "{}  checked: {} in {:.2}s",
+                    date(*time),
+                    result,
+                    duration
+                )?;
+                if let Some(until) = until {
+                    write!(f, ", {} times until {}", count, date(*until))?;
+                }
+                Ok(())
+            }
+            Self::Change { time, from, to } => write!(f, "{}  {} -> {}", date(*time), from, to),
+            Self::Snapshot {
+                time,
+                from,
+                to,
+                undo,
+                note,
+                author,
+            } => {
+                let what = if *undo { "snapshot undone" } else { "snapshot" };
+                write!(f, "{}  {}: {} -> {}", date(*time), what, from, to)?;
+                if let Some(author) = author {
+                    write!(f, " by {}", author)?;
+                }
+                if let Some(note) = note {
+                    write!(f, ", {}", note)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Who changed the snapshot, `UPS_AUTHOR` or else the user name
+fn author() -> Option<String> {
+    ["UPS_AUTHOR", "USER", "USERNAME"]
+        .into_iter()
+        .find_map(|key| std::env::var(key).ok().filter(|author| !author.is_empty()))
+}
+
+/// The value an undo goes back to, given the snapshot `current`
+///
+/// Refused when the snapshot isn't what ups last set it to, going back would lose a value
+/// the history doesn't know about
+pub fn undo<'a>(history: &'a [Entry], current: &str) -> Result<&'a str> {
+    let Some(Entry::Snapshot { from, to, .. }) = last_snapshot(history) else {
+        return Err("there is no snapshot to undo".into());
+    };
+    if to != current {
+        return Err(format!(
+            "it is {} but ups last set it to {}, it was changed outside of ups",
+            current, to
+        )
+        .into());
+    }
+    Ok(from)
+}
+
+/// The snapshot entry an undo would revert, the ones already undone are skipped
+fn last_snapshot(history: &[Entry]) -> Option<&Entry> {
+    let mut undone = 0;
+    for entry in history.iter().rev() {
+        match entry {
+            Entry::Snapshot { undo: true, .. } => undone += 1,
+            Entry::Snapshot { .. } if undone > 0 => undone -= 1,
+            Entry::Snapshot { .. } => return Some(entry),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn date(time: u64) -> humantime::Rfc3339Timestamp {
+    humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(time))
+}
+
+fn retention() -> Result<Duration> {
+    let days = match std::env::var("UPS_HISTORY_DAYS") {
+        Ok(days) => days.parse().map_err(|_| {
+            format!(
+                "UPS_HISTORY_DAYS: expected a number of days, got `{}`",
+                days
+            )
+        })?,
+        Err(_) => DEFAULT_DAYS,
+    };
+    Ok(Duration::from_secs(days * 24 * 60 * 60))
+}
+
+/// One JSON line per entry, so the file can be read with `tail` or `jq`
+fn path(name: &str) -> Result<PathBuf> {
+    // Escaped, an app name can hold slashes
+    let file = encode(name).replace('/', "%2F") + ".jsonl";
+    Ok(crate::data_dir()?.join("history").join(file))
+}
+
+pub fn read(name: &str) -> Result<Vec<Entry>> {
+    read_file(&path(name)?)
+}
+
+fn read_file(path: &Path) -> Result<Vec<Entry>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    std::fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str(line)
+                .map_err(|e| format!("Error while parsing {}: {}", path.display(), e).into())
+        })
+        .collect()
+}
+
+/// Adds the entries to the history of their apps, compacting repeated checks and dropping
+/// the ones older than the retention
+pub fn append(entries: &[(String, Entry)]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mut by_app: BTreeMap<&str, Vec<&Entry>> = BTreeMap::new();
+    for (name, entry) in entries {
+        by_app.entry(name).or_default().push(entry);
+    }
+    let oldest = now().saturating_sub(retention()?.as_secs());
+    let compaction = compaction();
+
+    let _lock = Lock::acquire(&crate::data_path()?)?;
+    for (name, new) in by_app {
+        let path = path(name)?;
+        let mut history = read_file(&path)?;
+        for entry in new {
+            let compacted =
+                compaction && history.last_mut().is_some_and(|last| last.compact(entry));
+            if !compacted {
+                history.push(entry.clone());
+            }
+        }
+        history
+            .retain(|entry| !matches!(entry, Entry::Check { .. }) || entry.last_time() >= oldest);
+
+        let mut lines = String::new();
+        for entry in &history {
+            lines += &serde_json::to_string(entry)?;
+            lines.push('\n');
+        }
+        std::fs::create_dir_all(path.parent().expect("Has a parent"))?;
+        data::write_atomic(&path, lines.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(from: &str, to: &str) -> Entry {
+        Entry::snapshot(from.to_owned(), to.to_owned(), false, None)
+    }
+
+    /// What `ups snapshot --undo` does, returns the restored value
+    fn undo_once(history: &mut Vec<Entry>, current: &mut String) -> Result<String> {
+        let from = undo(history, current)?.to_owned();
+        history.push(Entry::snapshot(current.clone(), from.clone(), true, None));
+        *current = from.clone();
+        Ok(from)
+    }
+
+    #[test]
+    fn repeated_undo_walks_back() {
+        let mut history = vec![
+            snapshot(crate::NONE, "1.0"),
+            Entry::check("ok".to_owned(), Duration::from_secs(1)),
+            Entry::change("1.0".to_owned(), "2.0".to_owned()),
+            snapshot("1.0", "2.0"),
+            snapshot("2.0", "3.0"),
+        ];
+        let mut current = "3.0".to_owned();
+        assert_eq!(undo_once(&mut history, &mut current).unwrap(), "2.0");
+        assert_eq!(undo_once(&mut history, &mut current).unwrap(), "1.0");
+        assert_eq!(undo_once(&mut history, &mut current).unwrap(), crate::NONE);
+        let error = undo_once(&mut history, &mut current).unwrap_err();
+        assert_eq!(error.to_string(), "there is no snapshot to undo");
+
+        // A new snapshot after undoing is undone first
+        history.push(snapshot(crate::NONE, "4.0"));
+        current = "4.0".to_owned();
+        assert_eq!(undo_once(&mut history, &mut current).unwrap(), crate::NONE);
+    }
+
+    #[test]
+    fn undo_between_snapshots() {
+        let mut history = vec![snapshot(crate::NONE, "1.0"), snapshot("1.0", "2.0")];
+        let mut current = "2.0".to_owned();
+        assert_eq!(undo_once(&mut history, &mut current).unwrap(), "1.0");
+        history.push(snapshot("1.0", "1.5"));
+        current = "1.5".to_owned();
+        assert_eq!(undo_once(&mut history, &mut current).unwrap(), "1.0");
+        assert_eq!(undo_once(&mut history, &mut current).unwrap(), crate::NONE);
+    }
+
+    #[test]
+    fn refuses_a_snapshot_changed_outside() {
+        let history = vec![snapshot(crate::NONE, "1.0"), snapshot("1.0", "2.0")];
+        for current in ["1.0", "1.7"] {
+            let error = undo(&history, current).unwrap_err().to_string();
+            assert!(error.contains("changed outside of ups"), "{}", error);
+        }
+        assert_eq!(undo(&history, "2.0").unwrap(), "1.0");
+        assert!(undo(&[], crate::NONE).is_err());
+    }
+
+    #[test]
+    fn compacts_repeated_checks() {
+        let mut first = Entry::check("ok".to_owned(), Duration::from_secs(1));
+        assert!(first.compact(&Entry::check("ok".to_owned(), Duration::from_secs(2))));
+        assert!(!first.compact(&Entry::check("timeout".to_owned(), Duration::from_secs(2))));
+        let Entry::Check {
+            count,
+            duration,
+            until,
+            ..
+        } = first
+        else {
+            unreachable!()
+        };
+        assert_eq!((count, duration, until.is_some()), (2, 2.0, true));
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex};
+use std::time::Instant;
+
+use serde::de::DeserializeOwned;
+use ureq::http::Response;
+use ureq::Body;
+
+use crate::{Result, TimedOut, Unavailable};
+
+const USER_AGENT: &str = concat!("ups/", env!("CARGO_PKG_VERSION"));
+
+/// Largest response body we accept, some registry documents are quite big
+const BODY_LIMIT: u64 = 64 * 1024 * 1024;
+
+/// Requests in flight to a single host unless `UPS_HOST_JOBS` says otherwise, so a big
+/// run doesn't get us rate limited by GitHub or the AUR
+const DEFAULT_HOST_JOBS: usize = 4;
+
+static IN_FLIGHT: Mutex<Option<HashMap<String, usize>>> = Mutex::new(None);
+static RELEASED: Condvar = Condvar::new();
+
+pub fn get_json<T: DeserializeOwned>(
+    url: &str,
+    headers: &[(&str, String)],
+    deadline: Instant,
+) -> Result<T> {
+    let _permit = Permit::acquire(url, deadline)?;
+    get(url, headers, deadline)?
+        .body_mut()
+        .with_config()
+        .limit(BODY_LIMIT)
+        .read_json()
+        .map_err(|e| error(url, e))
+}
+
+pub fn get_text(url: &str, headers: &[(&str, String)], deadline: Instant) -> Result<String> {
+    let _permit = Permit::acquire(url, deadline)?;
+    get(url, headers, deadline)?
+        .body_mut()
+        .with_config()
+        .limit(BODY_LIMIT)
+        .read_to_string()
+        .map_err(|e| error(url, e))
+}
+
+fn get(url: &str, headers: &[(&str, String)], deadline: Instant) -> Result<Response<Body>> {
+    let timeout = deadline.saturating_duration_since(Instant::now());
+    let mut request = ureq::get(url)
+        .config()
+        .timeout_global(Some(timeout))
+        .build()
+        .header("User-Agent", USER_AGENT);
+    for (key, value) in headers {
+        request = request.header(*key, value);
+    }
+    request.call().map_err(|e| error(url, e))
+}
+
+fn error(url: &str, e: ureq::Error) -> crate::Error {
+    match e {
+        ureq::Error::Timeout(_) => TimedOut.into(),
+        ureq::Error::StatusCode(code) if code == 429 || code >= 500 => {
+            Unavailable(format!("GET {}: {}", url, e)).into()
+        }
+        ureq::Error::Io(_) | ureq::Error::ConnectionFailed | ureq::Error::HostNotFound => {
+            Unavailable(format!("GET {}: {}", url, e)).into()
+        }
+        e => format!("GET {}: {}", url, e).into(),
+    }
+}
+
+/// A slot in the host's share of concurrent requests, held until the body is read
+struct Permit {
+    host: String,
+}
+
+impl Permit {
+    fn acquire(url: &str, deadline: Instant) -> Result<Self> {
+        let host = host(url).to_owned();
+        let limit = host_jobs()?;
+        let mut in_flight = IN_FLIGHT.lock().expect("Lock poisoned");
+        loop {
+            let count = in_flight
+                .get_or_insert_with(HashMap::new)
+                .entry(host.clone())
+                .or_default();
+            if *count < limit {
+                *count += 1;
+                return Ok(Self { host });
+            }
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return Err(TimedOut.into());
+            }
+            in_flight = RELEASED
+                .wait_timeout(in_flight, timeout)
+                .expect("Lock poisoned")
+                .0;
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut in_flight = IN_FLIGHT.lock().expect("Lock poisoned");
+        if let Some(count) = in_flight.as_mut().and_then(|m| m.get_mut(&self.host)) {
+            *count -= 1;
+        }
+        RELEASED.notify_all();
+    }
+}
+
+fn host_jobs() -> Result<usize> {
+    match std::env::var("UPS_HOST_JOBS") {
+        Ok(jobs) => match jobs.parse() {
+            Ok(jobs) if jobs > 0 => Ok(jobs),
+            _ => Err(format!("UPS_HOST_JOBS: expected a positive number, got `{}`", jobs).into()),
+        },
+        Err(_) => Ok(DEFAULT_HOST_JOBS),
+    }
+}
+
+/// `https://user@host:port/path` -> `host:port`
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host)
+}
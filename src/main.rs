@@ -1,22 +1,90 @@
 use std::io::Write;
-use std::path::Path;
-use std::{collections::HashMap, io::ErrorKind, path::PathBuf, process::Command};
+use std::time::{Duration, Instant, SystemTime};
+use std::{collections::HashMap, io::ErrorKind, path::PathBuf};
 
 use scolor::{Color, ColorDesc, ColorExt, CustomStyle, Effect};
 
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync + 'static>>;
+mod bundle;
+mod checker;
+mod config;
+mod data;
+mod history;
+mod http;
+mod normalize;
+mod options;
+mod outcome;
+mod pool;
+mod process;
+mod provider;
+mod select;
+mod store;
+mod version;
+
+use bundle::{Bundle, Conflict};
+use checker::{Checker, Exec, UNCHANGED_EXIT_CODE};
+use normalize::{normalize, Rule};
+use options::Options;
+use outcome::{Failure, FailureKind, Outcome};
+use provider::{Latest, RepoVersion};
+use version::{Scheme, Status};
+
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+type Result<T> = std::result::Result<T, Error>;
 
 const PURPLE_COLOR: ColorDesc = ColorDesc::rgb(100, 80, 250);
+const ORANGE_COLOR: ColorDesc = ColorDesc::rgb(255, 150, 0);
+const PINK_COLOR: ColorDesc = ColorDesc::rgb(230, 60, 140);
 const LIGHT_BLUE_UNDERLINE: CustomStyle<1, 1> = ([ColorDesc::light_blue()], [Effect::Underline]);
 
+/// The value of an app that was never checked successfully
 const NONE: &str = "NONE";
 
+/// Used when neither the app nor `UPS_TIMEOUT` set one
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+/// How many checks run at once unless `UPS_JOBS` says otherwise
+const DEFAULT_JOBS: usize = 8;
+/// Wait before the first retry, doubled for each one after
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// Values checked longer ago than this are shown as stale, unless `UPS_STALE_AFTER` says otherwise
+const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug)]
+struct TimedOut;
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Timed out")
+    }
+}
+impl std::error::Error for TimedOut {}
+
+/// An upstream error that may go away on its own, like a 5xx, a 429 or a network error
+#[derive(Debug)]
+struct Unavailable(String);
+impl std::fmt::Display for Unavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+impl std::error::Error for Unavailable {}
+
 fn main() -> Result<()> {
     let mut ups = Ups::default();
     let guard = Guard(&mut ups);
     let ups: &mut dyn ActionsInternal = guard.0;
 
     ups.load()?;
+    // Any command picks up config changes, `ups sync` is for when there is nothing else to do
+    let sync = ups.synced();
+    for name in &sync.added {
+        println!("{}", format!("Added `{}` from the config", name).green());
+    }
+    for name in &sync.removed {
+        println!(
+            "{}",
+            format!("Removed `{}`, it left the config", name).red()
+        );
+    }
 
     let args: Vec<String> = std::env::args().skip(1).collect();
     match args
@@ -25,17 +93,223 @@ fn main() -> Result<()> {
         .collect::<Vec<_>>()
         .as_slice()
     {
-        [] => {
-            ups.update_latest_value()?;
-            ups.print();
-        }
-        ["insert", name, script_path] => ups.insert((*name).to_string(), script_path)?,
-        ["remove", name] => ups.remove((*name).to_string())?,
-        ["snapshot", name] => ups.snapshot(name)?,
-        ["get", name] => println!("{}", ups.latest_value(name)?.tawait()?),
-        ["show", name] => {
-            let (path, content) = ups.show_script(name)?;
-            println!("{}\n{}", path.display().color(PURPLE_COLOR), content);
+        flags
+            if flags
+                .iter()
+                .all(|flag| ["--expand", "--no-fetch"].contains(flag)) =>
+        {
+            let names = ups.select(&["--all"])?;
+            let expand = flags.contains(&"--expand");
+            if flags.contains(&"--no-fetch") {
+                ups.print(&names, expand, Some(stale_after()?));
+            } else {
+                ups.update_latest_value(&names)?;
+                ups.print(&names, expand, None);
+            }
+        }
+        ["list", args @ ..] => {
+            let expand = |arg: &&str| *arg == "--expand";
+            let mut selectors: Vec<&str> =
+                args.iter().copied().filter(|arg| !expand(arg)).collect();
+            if selectors.is_empty() {
+                selectors.push("--all");
+            }
+            let names = ups.select(&selectors)?;
+            ups.print(&names, args.iter().any(expand), Some(stale_after()?));
+        }
+        ["check", args @ ..] if !args.is_empty() => {
+            let expand = |arg: &&str| *arg == "--expand";
+            let selectors: Vec<&str> = args.iter().copied().filter(|arg| !expand(arg)).collect();
+            let names = ups.select(&selectors)?;
+            ups.update_latest_value(&names)?;
+            ups.print(&names, args.iter().any(expand), None);
+        }
+        ["insert", name, args @ ..] => {
+            let (options, args) = Options::take(args)?;
+            ups.insert((*name).to_string(), Checker::from_args(&args)?, options)?
+        }
+        ["set", name, args @ ..] => match Options::take(args)? {
+            (options, rest) if rest.is_empty() => ups.set(name, options)?,
+            (_, rest) => return Err(format!("Unknown option `{}`", rest[0]).into()),
+        },
+        ["packaged", name, packaged @ ..] => match packaged {
+            [] => ups.set_packaged(name, None)?,
+            [packaged] => ups.set_packaged(name, Some(Checker::from_arg(packaged)?))?,
+            _ => println!("{}", usage()),
+        },
+        ["remove", args @ ..] if !args.is_empty() => {
+            let yes = |arg: &&str| matches!(*arg, "--yes" | "-y");
+            let selectors: Vec<&str> = args.iter().copied().filter(|arg| !yes(arg)).collect();
+            let names = ups.select(&selectors)?;
+            if names.len() > 1
+                && !args.iter().any(yes)
+                && !confirm(&format!(
+                    "Remove {} apps: {}?",
+                    names.len(),
+                    names.join(", ")
+                ))?
+            {
+                println!("Nothing removed");
+                return Ok(());
+            }
+            ups.remove(&names)?;
+            for name in &names {
+                println!("Removed `{}`", name);
+            }
+        }
+        ["export", args @ ..] => {
+            let mut embed_scripts = false;
+            let mut file = None;
+            for arg in args {
+                match *arg {
+                    "--embed-scripts" => embed_scripts = true,
+                    arg if !arg.starts_with("--") && file.is_none() => file = Some(arg),
+                    arg => return Err(format!("Unknown option `{}`", arg).into()),
+                }
+            }
+            let bundle = ups.export(embed_scripts)?;
+            match file {
+                Some(file) if file != "-" => std::fs::write(file, bundle.to_string())?,
+                _ => print!("{}", bundle),
+            }
+        }
+        ["import", file, args @ ..] => {
+            let on_conflict = match args {
+                [] => Conflict::default(),
+                ["--on-conflict", mode] => mode.parse()?,
+                _ => return Err(format!("Unknown option `{}`", args[0]).into()),
+            };
+            let bundle = match *file {
+                "-" => std::io::read_to_string(std::io::stdin())?,
+                file => std::fs::read_to_string(file)?,
+            };
+            for (name, imported) in ups.import(Bundle::parse(&bundle)?, on_conflict)? {
+                println!("{}: {}", name.yellow(), imported);
+            }
+        }
+        ["sync"] => {
+            let sync = ups.synced();
+            if sync.added.is_empty() && sync.removed.is_empty() {
+                println!("Already in sync with {}", config_path()?.display());
+            }
+        }
+        ["snapshot", args @ ..] if !args.is_empty() => {
+            let mut selectors = vec![];
+            let mut to = None;
+            let mut note = None;
+            let mut undo = false;
+            let mut yes = false;
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                let mut value = || args.next().ok_or(format!("Missing value for `{}`", arg));
+                match *arg {
+                    "--undo" => undo = true,
+                    "--yes" | "-y" => yes = true,
+                    "--to" => to = Some(value()?.to_string()),
+                    "--note" => note = Some(value()?.to_string()),
+                    selector => selectors.push(selector),
+                }
+            }
+            let names = ups.select(&selectors)?;
+            let mut errors = vec![];
+            if undo {
+                if to.is_some() {
+                    return Err("`--undo` and `--to` don't go together".into());
+                }
+                if names.len() > 1
+                    && !yes
+                    && !confirm(&format!(
+                        "Undo the last snapshot of {} apps: {}?",
+                        names.len(),
+                        names.join(", ")
+                    ))?
+                {
+                    println!("Nothing undone");
+                    return Ok(());
+                }
+                for name in &names {
+                    match ups.undo_snapshot(name, note.clone()) {
+                        Ok(restored) => println!("Snapshot of `{}` is back to {}", name, restored),
+                        Err(e) => errors.push(e.to_string()),
+                    }
+                }
+                return report(errors, names.len(), "snapshots could not be undone");
+            }
+            let mut changes = vec![];
+            let mut unchanged = 0;
+            for (name, snapshotted) in ups.snapshot_values(&names, to)? {
+                match snapshotted {
+                    Ok((from, to)) if from == to => {
+                        unchanged += 1;
+                        println!("{}: already at {}", name.yellow(), to);
+                    }
+                    Ok((from, to)) => {
+                        println!("{}: {} -> {}", name.yellow(), from, to);
+                        changes.push((name, to));
+                    }
+                    Err(failure) => errors.push(format!("Could not check `{}`: {}", name, failure)),
+                }
+            }
+            // Every change above overwrites a snapshot, a wrong selector shouldn't do that
+            // to a whole group of apps without asking
+            if changes.len() > 1
+                && !yes
+                && !confirm(&format!("Snapshot these {} apps?", changes.len()))?
+            {
+                println!("Nothing snapshotted");
+                changes.clear();
+            }
+            ups.snapshot(&changes, note);
+            if names.len() > 1 {
+                println!(
+                    "Snapshots: {} changed, {} already up to date, {} failed",
+                    changes.len(),
+                    unchanged,
+                    errors.len()
+                );
+            }
+            report(errors, names.len(), "apps could not be checked")?;
+        }
+        ["edit", name] => ups.edit(name)?,
+        ["get", selectors @ ..] if !selectors.is_empty() => {
+            let names = ups.select(selectors)?;
+            let mut errors = vec![];
+            for (name, outcome) in ups.latest_value(&names)? {
+                match outcome.latest {
+                    Ok(latest) if names.len() == 1 => println!("{}", latest.value),
+                    Ok(latest) => println!("{}: {}", name.yellow(), latest.value),
+                    Err(failure) => errors.push(format!("Could not check `{}`: {}", name, failure)),
+                }
+            }
+            report(errors, names.len(), "apps could not be checked")?;
+        }
+        ["show", selectors @ ..] if !selectors.is_empty() => {
+            let names = ups.select(selectors)?;
+            for (i, name) in names.iter().enumerate() {
+                if names.len() > 1 {
+                    let gap = if i == 0 { "" } else { "\n" };
+                    println!("{}{}", gap, name.yellow().bold::<1>());
+                }
+                let (checker, content) = ups.show_script(name)?;
+                println!("{}", checker.color(PURPLE_COLOR));
+                for (key, value) in ups.details(name)? {
+                    println!("{}: {}", key.yellow(), value);
+                }
+                if let Some(content) = content {
+                    println!("{}", content);
+                }
+            }
+        }
+        ["history", name] => {
+            for entry in ups.history(name)? {
+                let color = match &entry {
+                    history::Entry::Change { .. } => ColorDesc::yellow(),
+                    history::Entry::Snapshot { .. } => PURPLE_COLOR,
+                    history::Entry::Check { result, .. } if result == "ok" => ColorDesc::green(),
+                    history::Entry::Check { .. } => PINK_COLOR,
+                };
+                println!("{}", entry.to_string().color(color));
+            }
         }
         _ => println!("{}", usage()),
     }
@@ -43,14 +317,50 @@ fn main() -> Result<()> {
 }
 
 trait Actions {
-    fn update_latest_value(&mut self) -> Result<()>;
-    fn print(&self);
-    fn insert(&mut self, name: String, script_path: &str) -> Result<()>;
-    fn remove(&mut self, name: String) -> Result<()>;
-    fn snapshot(&mut self, name: &str) -> Result<()>;
-    fn latest_value(&self, name: &str) -> Result<std::thread::JoinHandle<Result<String>>>;
-    fn show_script(&self, name: &str) -> Result<(PathBuf, String)>;
+    /// Checks the apps, the others keep their values
+    fn update_latest_value(&mut self, names: &[String]) -> Result<()>;
+    /// With `stale_after`, also shows when each app was checked, highlighting the ones
+    /// checked longer ago
+    fn print(&self, names: &[String], expanded: bool, stale_after: Option<Duration>);
+    fn insert(&mut self, name: String, checker: Checker, options: Options) -> Result<()>;
+    fn set(&mut self, name: &str, options: Options) -> Result<()>;
+    fn set_packaged(&mut self, name: &str, packaged: Option<Checker>) -> Result<()>;
+    /// Removes all of them or, if one can't be, none
+    fn remove(&mut self, names: &[String]) -> Result<()>;
+    /// Snapshots the latest values, or `to` without running the checks
+    /// The current and new snapshot of the apps, checking them unless `to` is given, nothing
+    /// is set until `snapshot` is called with the ones to change
+    fn snapshot_values(
+        &mut self,
+        names: &[String],
+        to: Option<String>,
+    ) -> Result<Vec<(String, Snapshotted)>>;
+    fn snapshot(&mut self, values: &[(String, String)], note: Option<String>);
+    /// Reverts the last snapshot that wasn't undone yet, returning the restored value
+    fn undo_snapshot(&mut self, name: &str, note: Option<String>) -> Result<String>;
+    /// Opens the app's script in the editor, then checks it still works
+    fn edit(&mut self, name: &str) -> Result<()>;
+    /// Checks the apps without recording anything
+    fn latest_value(&self, names: &[String]) -> Result<Vec<(String, Outcome)>>;
+    /// The registered apps matching names, patterns, `@tags` or `--all`
+    fn select(&self, selectors: &[&str]) -> Result<Vec<String>>;
+    fn show_script(&self, name: &str) -> Result<(String, Option<String>)>;
+    fn details(&self, name: &str) -> Result<Vec<(&'static str, String)>>;
+    /// Oldest first, the history outlives the app so a removed one can still be looked up
+    fn history(&self, name: &str) -> Result<Vec<history::Entry>>;
+    /// What loading the config file changed
+    fn synced(&self) -> &config::Sync;
+    fn export(&self, embed_scripts: bool) -> Result<Bundle>;
+    /// Returns what happened to each app of the bundle
+    fn import(
+        &mut self,
+        bundle: Bundle,
+        on_conflict: Conflict,
+    ) -> Result<Vec<(String, &'static str)>>;
 }
+/// An app's previous and new snapshot, or why its check failed
+type Snapshotted = std::result::Result<(String, String), Failure>;
+
 trait ActionsInternal: Actions {
     fn load(&mut self) -> Result<()>;
     fn save(&self) -> Result<()>;
@@ -64,37 +374,355 @@ impl Drop for Guard<'_> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct App {
-    script_path: PathBuf,
+    checker: Checker,
     latest_value: String,
     snapshot_value: String,
+    /// Where the version we currently ship comes from, e.g. the AUR package
+    packaged: Option<Checker>,
+    packaged_value: String,
+    /// Versions in other repositories, filled by providers like Repology
+    repos: Vec<RepoVersion>,
+    scheme: Scheme,
+    /// Applied to fetched values before they are compared and stored
+    normalize: Vec<Rule>,
+    /// The latest value before normalization
+    raw_latest_value: String,
+    /// Overrides the global timeout
+    timeout: Option<Duration>,
+    /// Overrides the global retries
+    retries: Option<u32>,
+    /// Why the last check failed, `latest_value` is then the last good one
+    failure: Option<Failure>,
+    /// How long the last check took
+    duration: Option<Duration>,
+    /// When the last check ran
+    checked: Option<SystemTime>,
+    /// Declared in the config file, which then owns its settings
+    from_config: bool,
+    /// Arguments, environment and working directory of the script or command
+    exec: Exec,
+    /// Select groups of apps with `@tag`
+    tags: Vec<String>,
+}
+
+impl App {
+    fn new(checker: Checker) -> Self {
+        Self {
+            checker,
+            latest_value: NONE.to_owned(),
+            snapshot_value: NONE.to_owned(),
+            packaged: None,
+            packaged_value: NONE.to_owned(),
+            repos: vec![],
+            scheme: Scheme::default(),
+            normalize: vec![],
+            raw_latest_value: NONE.to_owned(),
+            timeout: None,
+            retries: None,
+            failure: None,
+            duration: None,
+            checked: None,
+            from_config: false,
+            exec: Exec::default(),
+            tags: vec![],
+        }
+    }
+
+    /// The app's own timeout, or else the global one
+    fn timeout(&self) -> Result<Duration> {
+        if let Some(timeout) = self.timeout {
+            return Ok(timeout);
+        }
+        match std::env::var("UPS_TIMEOUT") {
+            Ok(timeout) => options::parse_seconds(&timeout),
+            Err(_) => Ok(DEFAULT_TIMEOUT),
+        }
+    }
+
+    /// How many times a check that failed for a retryable reason is tried again
+    fn retries(&self) -> Result<u32> {
+        if let Some(retries) = self.retries {
+            return Ok(retries);
+        }
+        match std::env::var("UPS_RETRIES") {
+            Ok(retries) => options::parse_retries(&retries),
+            Err(_) => Ok(0),
+        }
+    }
+
+    fn job(&self, name: &str) -> Result<Job> {
+        let previous = Latest {
+            value: self.latest_value.clone(),
+            raw: self.raw_latest_value.clone(),
+            repos: self.repos.clone(),
+            unchanged: false,
+        };
+        Ok(Job {
+            label: name.to_owned(),
+            checker: self.checker.clone(),
+            exec: self.context(name, &previous.value, self.exec.clone())?,
+            previous,
+            rules: self.normalize.clone(),
+            timeout: self.timeout()?,
+            retries: self.retries()?,
+        })
+    }
+
+    /// Tells the script which app it checks, the app's own variables come on top
+    fn context(&self, name: &str, previous: &str, mut exec: Exec) -> Result<Exec> {
+        let context = [
+            ("UPS_APP_NAME", name.to_owned()),
+            ("UPS_SNAPSHOT_VALUE", self.snapshot_value.clone()),
+            ("UPS_PREVIOUS_LATEST", previous.to_owned()),
+            ("UPS_DATA_DIR", data_dir()?.display().to_string()),
+            ("UPS_UNCHANGED_EXIT_CODE", UNCHANGED_EXIT_CODE.to_string()),
+        ];
+        for (key, value) in context {
+            exec.env.entry(key.to_owned()).or_insert(value);
+        }
+        Ok(exec)
+    }
+
+    /// Packaged values are compared as they come, without normalization
+    fn packaged_job(&self, name: &str) -> Result<Option<Job>> {
+        let Some(packaged) = &self.packaged else {
+            return Ok(None);
+        };
+        Ok(Some(Job {
+            label: format!("{} (packaged)", name),
+            checker: packaged.clone(),
+            exec: self.context(name, &self.packaged_value, Exec::default())?,
+            previous: self.packaged_value.clone().into(),
+            rules: vec![],
+            timeout: self.timeout()?,
+            retries: self.retries()?,
+        }))
+    }
+
+    /// The checker with its arguments, what tells apps sharing a script apart
+    fn source(&self) -> String {
+        std::iter::once(self.checker.to_string())
+            .chain(self.exec.args.iter().map(|arg| quote(arg)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Points the checker and packaged source to copies in the store
+    fn store_scripts(&mut self) -> Result<()> {
+        for checker in std::iter::once(&mut self.checker).chain(&mut self.packaged) {
+            if let Checker::Script(path) = checker {
+                if !store::contains(path)? {
+                    *path = store::add_file(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Keeps the previous value when the check failed
+    fn record(&mut self, outcome: Outcome) {
+        self.duration = Some(outcome.duration);
+        self.checked = Some(SystemTime::now());
+        match outcome.latest {
+            Ok(latest) => {
+                self.set_latest(latest);
+                self.failure = None;
+            }
+            Err(failure) => self.failure = Some(failure),
+        }
+    }
+
+    fn set_latest(&mut self, latest: Latest) {
+        self.latest_value = latest.value;
+        self.raw_latest_value = latest.raw;
+        self.repos = latest.repos;
+    }
+
+    fn apply(&mut self, options: Options) {
+        if let Some(packaged) = options.packaged {
+            // The config applies its options on every load, that alone shouldn't forget the value
+            if packaged.as_ref().map(ToString::to_string)
+                != self.packaged.as_ref().map(ToString::to_string)
+            {
+                self.packaged_value = NONE.to_owned();
+            }
+            self.packaged = packaged;
+        }
+        if let Some(scheme) = options.scheme {
+            self.scheme = scheme;
+        }
+        if let Some(rules) = options.normalize {
+            self.normalize = rules;
+            self.latest_value = normalize(&self.normalize, &self.raw_latest_value);
+        }
+        if let Some(timeout) = options.timeout {
+            self.timeout = timeout;
+        }
+        if let Some(retries) = options.retries {
+            self.retries = retries;
+        }
+        if let Some(args) = options.args {
+            self.exec.args = args;
+        }
+        if let Some(env) = options.env {
+            self.exec.env = env;
+        }
+        if let Some(cwd) = options.cwd {
+            self.exec.cwd = cwd;
+        }
+        if let Some(tags) = options.tags {
+            self.tags = tags;
+        }
+    }
 }
 
 #[derive(Default)]
 struct Ups {
     apps: HashMap<String, App>,
+    /// What was loaded, saving only writes the apps that changed since
+    base: data::Base,
+    synced: config::Sync,
+    /// Checks and value changes to add to the history on save
+    history: Vec<(String, history::Entry)>,
 }
 
-impl Actions for Ups {
-    fn update_latest_value(&mut self) -> Result<()> {
-        let apps: Vec<_> = self.apps.iter().map(|(name, _)| name.clone()).collect();
-        let mut new_values = vec![];
-        for name in apps {
-            let latest_value = self.latest_value(&name)?;
-            new_values.push((name, latest_value));
-        }
-        let new_values: Vec<_> = new_values
-            .into_iter()
-            .map(|(n, v)| (n, v.tawait()))
+impl Ups {
+    /// The app, if its settings can be changed from the command line
+    fn editable(&mut self, name: &str) -> Result<&mut App> {
+        let app = self
+            .apps
+            .get_mut(name)
+            .ok_or(format!("App `{}` is not registered.", name))?;
+        if app.from_config {
+            return Err(format!(
+                "App `{}` is declared in {}, change it there",
+                name,
+                config_path()?.display()
+            )
+            .into());
+        }
+        Ok(app)
+    }
+
+    /// Records the outcome on the app, and the check and any new value in its history
+    fn record(&mut self, name: &str, outcome: Outcome) {
+        let app = self.apps.get_mut(name).expect("Already checked");
+        let result = match &outcome.latest {
+            Ok(_) => "ok".to_owned(),
+            Err(failure) => failure.status(),
+        };
+        let duration = outcome.duration;
+        let previous = app.latest_value.clone();
+        app.record(outcome);
+        self.history
+            .push((name.to_owned(), history::Entry::check(result, duration)));
+        if app.latest_value != previous {
+            let change = history::Entry::change(previous, app.latest_value.clone());
+            self.history.push((name.to_owned(), change));
+        }
+    }
+
+    /// Sets the snapshot value, logging the change in the app's history
+    fn set_snapshot(&mut self, name: &str, to: String, undo: bool, note: Option<String>) {
+        let app = self.apps.get_mut(name).expect("Already checked");
+        if app.snapshot_value == to {
+            return;
+        }
+        let from = std::mem::replace(&mut app.snapshot_value, to.clone());
+        let entry = history::Entry::snapshot(from, to, undo, note);
+        self.history.push((name.to_owned(), entry));
+    }
+
+    /// Puts `app` in place of the one named `name`, if any, logging the snapshot change
+    fn replace(&mut self, name: &str, mut app: App, note: &str) {
+        let snapshot = std::mem::replace(
+            &mut app.snapshot_value,
+            self.apps
+                .get(name)
+                .map_or_else(|| NONE.to_owned(), |ours| ours.snapshot_value.clone()),
+        );
+        self.apps.insert(name.to_owned(), app);
+        self.set_snapshot(name, snapshot, false, Some(note.to_owned()));
+    }
+
+    /// Removes the app, logging that its snapshot is gone
+    fn remove_app(&mut self, name: &str, note: &str) {
+        self.set_snapshot(name, NONE.to_owned(), false, Some(note.to_owned()));
+        self.apps.remove(name);
+    }
+
+    /// Config apps take their settings from the config and keep their values, apps that
+    /// were removed from it are removed here too
+    fn merge_config(&mut self) -> Result<()> {
+        let config_path = config_path()?;
+        let declared = match config_path.exists() {
+            true => config::read(&config_path)?,
+            // A typo shouldn't remove every app
+            false if std::env::var_os("UPS_CONFIG").is_some() => {
+                return Err(format!("UPS_CONFIG: {} does not exist", config_path.display()).into())
+            }
+            false => Default::default(),
+        };
+
+        let removed: Vec<String> = self
+            .apps
+            .iter()
+            .filter(|(name, app)| app.from_config && !declared.contains_key(*name))
+            .map(|(name, _)| name.clone())
             .collect();
-        for (n, v) in new_values {
-            self.apps.get_mut(&n).expect("Already checked").latest_value = v?;
+        for name in removed {
+            self.remove_app(&name, "removed from the config");
+            self.synced.removed.push(name);
+        }
+        for (name, (checker, options)) in declared {
+            let app = self
+                .apps
+                .entry(name.clone())
+                .or_insert_with(|| App::new(checker.clone()));
+            if !app.from_config {
+                self.synced.added.push(name);
+            }
+            app.checker = checker;
+            app.apply(options);
+            app.from_config = true;
+        }
+        Ok(())
+    }
+}
+
+impl Actions for Ups {
+    fn update_latest_value(&mut self, names: &[String]) -> Result<()> {
+        let mut jobs = vec![];
+        for name in names {
+            let app = self
+                .apps
+                .get(name)
+                .ok_or(format!("App `{}` is not registered.", name))?;
+            jobs.push((name.clone(), false, app.job(name)?));
+            if let Some(job) = app.packaged_job(name)? {
+                jobs.push((name.clone(), true, job));
+            }
+        }
+        let new_values = pool::map(max_jobs()?, jobs, |(name, packaged, job)| {
+            (name, packaged, job.run())
+        });
+        for (name, packaged, outcome) in new_values {
+            if !packaged {
+                self.record(&name, outcome?);
+                continue;
+            }
+            let app = self.apps.get_mut(&name).expect("Already checked");
+            match outcome?.latest {
+                Ok(latest) => app.packaged_value = latest.value,
+                Err(failure) => eprintln!("`{}` packaged source: {}", name, failure),
+            }
         }
         Ok(())
     }
 
-    fn print(&self) {
+    fn print(&self, names: &[String], expanded: bool, stale_after: Option<Duration>) {
         use term_table::row::Row;
         use term_table::table_cell::TableCell;
         use term_table::{Table, TableStyle};
@@ -102,151 +730,608 @@ impl Actions for Ups {
         let mut table = Table::new();
         table.style = TableStyle::rounded();
 
-        table.add_row(Row::new(vec![
+        let apps: Vec<(&String, &App)> = names
+            .iter()
+            .filter_map(|name| self.apps.get_key_value(name))
+            .collect();
+
+        // Only worth a column when some app tracks what is packaged
+        let show_packaged = apps.iter().any(|(_, app)| app.packaged.is_some());
+
+        let mut header = vec![
             TableCell::new("App".custom(LIGHT_BLUE_UNDERLINE)),
             TableCell::new("SnapshotValue".custom(LIGHT_BLUE_UNDERLINE)),
             TableCell::new("LatestValue".custom(LIGHT_BLUE_UNDERLINE)),
-            TableCell::new("ScriptPath".custom(LIGHT_BLUE_UNDERLINE)),
-        ]));
-
-        let mut apps: Vec<(&String, &App)> = self.apps.iter().collect();
-        apps.sort_by_key(|(name, _)| *name);
+            TableCell::new("Status".custom(LIGHT_BLUE_UNDERLINE)),
+        ];
+        if stale_after.is_some() {
+            header.push(TableCell::new("Checked".custom(LIGHT_BLUE_UNDERLINE)));
+        }
+        if show_packaged {
+            header.push(TableCell::new("PackagedValue".custom(LIGHT_BLUE_UNDERLINE)));
+        }
+        header.push(TableCell::new("Source".custom(LIGHT_BLUE_UNDERLINE)));
+        table.add_row(Row::new(header));
 
         for (name, app) in apps {
-            let diff_color = if app.snapshot_value == app.latest_value {
-                scolor::green
-            } else {
-                scolor::red
-            };
-            table.add_row(Row::new(vec![
+            let status = app.scheme.status(&app.snapshot_value, &app.latest_value);
+            let mut row = vec![
                 TableCell::new(name.yellow().bold::<1>()),
-                TableCell::new(diff_color(&app.snapshot_value)),
-                TableCell::new(diff_color(&app.latest_value)),
-                TableCell::new(app.script_path.display().color(PURPLE_COLOR).italic::<1>()),
-            ]));
+                TableCell::new(app.snapshot_value.color(status_color(status))),
+                TableCell::new(app.latest_value.color(status_color(status))),
+                match &app.failure {
+                    Some(failure) => TableCell::new(failure.status().color(PINK_COLOR)),
+                    None => TableCell::new(status_name(status).color(status_color(status))),
+                },
+            ];
+            if let Some(stale_after) = stale_after {
+                let age = app
+                    .checked
+                    .map(|checked| checked.elapsed().unwrap_or_default());
+                row.push(match age {
+                    Some(age) if age > stale_after => TableCell::new(ago(age).color(ORANGE_COLOR)),
+                    Some(age) => TableCell::new(ago(age)),
+                    None => TableCell::new("never".color(ORANGE_COLOR)),
+                });
+            }
+            if show_packaged {
+                row.push(match app.packaged {
+                    Some(_) => {
+                        let status = app.scheme.status(&app.packaged_value, &app.latest_value);
+                        TableCell::new(app.packaged_value.color(status_color(status)))
+                    }
+                    None => TableCell::new(""),
+                });
+            }
+            row.push(TableCell::new(
+                app.source().color(PURPLE_COLOR).italic::<1>(),
+            ));
+            table.add_row(Row::new(row));
+
+            if expanded {
+                for repo in &app.repos {
+                    let (version, status) = if repo.is_outdated() {
+                        (repo.version.red(), repo.status.red())
+                    } else {
+                        (repo.version.green(), repo.status.green())
+                    };
+                    let mut row = vec![
+                        TableCell::new(format!("  {}", repo.repo)),
+                        TableCell::new(""),
+                        TableCell::new(version),
+                        TableCell::new(""),
+                    ];
+                    if stale_after.is_some() {
+                        row.push(TableCell::new(""));
+                    }
+                    if show_packaged {
+                        row.push(TableCell::new(""));
+                    }
+                    row.push(TableCell::new(status));
+                    table.add_row(Row::new(row));
+                }
+            }
         }
         println!("\n{}", table.render());
     }
 
-    fn insert(&mut self, name: String, script_path: &str) -> Result<()> {
-        self.apps.insert(
-            name,
-            App {
-                script_path: Path::new(script_path).canonicalize()?,
-                latest_value: NONE.to_owned(),
-                snapshot_value: NONE.to_owned(),
-            },
-        );
+    fn insert(&mut self, name: String, checker: Checker, options: Options) -> Result<()> {
+        if self.apps.contains_key(&name) {
+            self.editable(&name)?;
+        }
+        let mut app = App::new(checker);
+        let store = options.store;
+        app.apply(options);
+        if store {
+            app.store_scripts()?;
+        }
+        self.replace(&name, app, "inserted");
         Ok(())
     }
 
-    fn remove(&mut self, name: String) -> Result<()> {
-        if self.apps.remove(&name).is_none() {
-            return Err("App does not exist".into());
+    fn set(&mut self, name: &str, options: Options) -> Result<()> {
+        let app = self.editable(name)?;
+        let store = options.store;
+        app.apply(options);
+        if store {
+            app.store_scripts()?;
         }
         Ok(())
     }
 
-    fn snapshot(&mut self, name: &str) -> Result<()> {
-        let latest_value = self.latest_value(name)?.tawait()?;
-        let app = self.apps.get_mut(name).expect("Already checked");
-        app.latest_value = latest_value.clone();
-        app.snapshot_value = latest_value;
+    fn set_packaged(&mut self, name: &str, packaged: Option<Checker>) -> Result<()> {
+        let app = self.editable(name)?;
+        app.packaged = packaged;
+        app.packaged_value = NONE.to_owned();
         Ok(())
     }
 
-    fn latest_value(&self, name: &str) -> Result<std::thread::JoinHandle<Result<String>>> {
-        let app = self
-            .apps
-            .get(name)
-            .ok_or(format!("App `{}` is not registered.", name))?;
-        let script_path = app.script_path.clone();
-        let name = name.to_owned();
-
-        Ok(std::thread::spawn(move || {
-            println!(
-                "{}",
-                format!("Fetching latest value of `{}` app...", name).yellow()
-            );
-            std::io::stdout().flush()?;
-
-            let output = Command::new(script_path).output()?;
-            let value = String::from_utf8(output.stdout)?;
-            let value = value.trim();
-
-            if output.status.success() && !value.is_empty() {
-                Ok(value.to_owned())
-            } else {
-                Ok(NONE.to_owned())
+    fn remove(&mut self, names: &[String]) -> Result<()> {
+        for name in names {
+            self.editable(name)?;
+        }
+        for name in names {
+            self.remove_app(name, "removed");
+        }
+        Ok(())
+    }
+
+    fn snapshot_values(
+        &mut self,
+        names: &[String],
+        to: Option<String>,
+    ) -> Result<Vec<(String, Snapshotted)>> {
+        let values: Vec<(String, std::result::Result<String, Failure>)> = match to {
+            Some(to) => names
+                .iter()
+                .map(|name| (name.clone(), Ok(to.clone())))
+                .collect(),
+            None => {
+                let mut values = vec![];
+                for (name, outcome) in self.latest_value(names)? {
+                    self.record(&name, outcome);
+                    let app = &self.apps[&name];
+                    let value = match &app.failure {
+                        Some(failure) => Err(failure.clone()),
+                        None => Ok(app.latest_value.clone()),
+                    };
+                    values.push((name, value));
+                }
+                values
             }
-        }))
+        };
+        Ok(values
+            .into_iter()
+            .map(|(name, value)| {
+                let from = self.apps[&name].snapshot_value.clone();
+                let result = value.map(|to| (from, to));
+                (name, result)
+            })
+            .collect())
     }
 
-    fn show_script(&self, name: &str) -> Result<(PathBuf, String)> {
+    fn snapshot(&mut self, values: &[(String, String)], note: Option<String>) {
+        for (name, to) in values {
+            self.set_snapshot(name, to.clone(), false, note.clone());
+        }
+    }
+
+    fn undo_snapshot(&mut self, name: &str, note: Option<String>) -> Result<String> {
+        if !self.apps.contains_key(name) {
+            return Err(format!("App `{}` is not registered.", name).into());
+        }
+        let mut history = history::read(name)?;
+        history.extend(
+            self.history
+                .iter()
+                .filter(|(n, _)| n == name)
+                .map(|(_, entry)| entry.clone()),
+        );
+        let from = history::undo(&history, &self.apps[name].snapshot_value)
+            .map_err(|e| format!("Can't undo the snapshot of `{}`: {}", name, e))?
+            .to_owned();
+        self.set_snapshot(name, from.clone(), true, note);
+        Ok(from)
+    }
+
+    fn edit(&mut self, name: &str) -> Result<()> {
+        let app = self.editable(name)?;
+        let path = app
+            .checker
+            .script_path()
+            .ok_or(format!("App `{}` is not checked by a script", name))?
+            .to_owned();
+        // A stored copy may be shared by other apps, it is edited as a new one
+        let stored = store::contains(&path)?;
+        let edited = if stored {
+            let extension = path.extension().and_then(|extension| extension.to_str());
+            let (scratch, mut file) = store::scratch(extension)?;
+            std::io::copy(&mut std::fs::File::open(&path)?, &mut file)?;
+            scratch
+        } else {
+            path.clone()
+        };
+
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_owned());
+        // Through the shell, editors are often given with arguments like `code --wait`
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(&edited)
+            .status()?;
+        if !status.success() {
+            if stored {
+                std::fs::remove_file(&edited)?;
+            }
+            return Err(format!("`{}` exited with {}", editor, status).into());
+        }
+        let script = if stored {
+            let script = store::add_file(&edited)?;
+            std::fs::remove_file(&edited)?;
+            script
+        } else {
+            path
+        };
+
+        let outcome = Job {
+            checker: Checker::Script(script.clone()),
+            ..app.job(name)?
+        }
+        .run()?;
+        if let Err(failure) = &outcome.latest {
+            let failure = failure.to_string();
+            if stored {
+                return Err(format!(
+                    "The edited script failed, `{}` keeps the previous one, the edit is saved as {}:\n{}",
+                    name,
+                    script.display(),
+                    failure
+                )
+                .into());
+            }
+            self.record(name, outcome);
+            return Err(format!("The edited script failed:\n{}", failure).into());
+        }
+        app.checker = Checker::Script(script);
+        self.record(name, outcome);
+        println!("{}", self.apps[name].latest_value);
+        Ok(())
+    }
+
+    fn latest_value(&self, names: &[String]) -> Result<Vec<(String, Outcome)>> {
+        let mut jobs = vec![];
+        for name in names {
+            let app = self
+                .apps
+                .get(name)
+                .ok_or(format!("App `{}` is not registered.", name))?;
+            jobs.push((name.clone(), app.job(name)?));
+        }
+        pool::map(max_jobs()?, jobs, |(name, job)| Ok((name, job.run()?)))
+            .into_iter()
+            .collect()
+    }
+
+    fn select(&self, selectors: &[&str]) -> Result<Vec<String>> {
+        select::select(selectors, &self.apps)
+    }
+
+    fn show_script(&self, name: &str) -> Result<(String, Option<String>)> {
         let app = self
             .apps
             .iter()
             .find(|(n, _)| n == &name)
             .ok_or("Unknown script")?;
-        Ok((
-            app.1.script_path.clone(),
-            std::fs::read_to_string(&app.1.script_path)?
-                .trim()
-                .to_owned(),
-        ))
+        let content = match app.1.checker.script_path() {
+            Some(path) => Some(std::fs::read_to_string(path)?.trim().to_owned()),
+            None => None,
+        };
+        Ok((app.1.checker.to_string(), content))
+    }
+
+    fn details(&self, name: &str) -> Result<Vec<(&'static str, String)>> {
+        let app = self
+            .apps
+            .get(name)
+            .ok_or(format!("App `{}` is not registered.", name))?;
+        let mut details = vec![];
+        if let Some(packaged) = &app.packaged {
+            details.push(("packaged", packaged.to_string()));
+        }
+        if let Some(path) = app.checker.script_path() {
+            if store::contains(path)? {
+                details.push(("script", "stored copy, `ups edit` to change it".to_owned()));
+            }
+        }
+        if !app.exec.args.is_empty() {
+            let args: Vec<_> = app.exec.args.iter().map(|arg| quote(arg)).collect();
+            details.push(("args", args.join(" ")));
+        }
+        if !app.exec.env.is_empty() {
+            let env: Vec<_> = app
+                .exec
+                .env
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, quote(value)))
+                .collect();
+            details.push(("env", env.join(" ")));
+        }
+        if let Some(cwd) = &app.exec.cwd {
+            details.push(("cwd", cwd.display().to_string()));
+        }
+        if !app.tags.is_empty() {
+            details.push(("tags", app.tags.join(", ")));
+        }
+        details.push(("scheme", app.scheme.to_string()));
+        if let Some(timeout) = app.timeout {
+            details.push(("timeout", format!("{}s", timeout.as_secs_f64())));
+        }
+        if let Some(retries) = app.retries {
+            details.push(("retries", retries.to_string()));
+        }
+        if !app.normalize.is_empty() {
+            let rules: Vec<_> = app.normalize.iter().map(ToString::to_string).collect();
+            details.push(("normalize", rules.join(" ")));
+        }
+        details.push(("snapshot", app.snapshot_value.clone()));
+        details.push(("latest", app.latest_value.clone()));
+        if app.raw_latest_value != app.latest_value {
+            details.push(("raw latest", app.raw_latest_value.clone()));
+        }
+        if let Some(duration) = app.duration {
+            let result = match &app.failure {
+                Some(failure) => failure.status(),
+                None => "ok".to_owned(),
+            };
+            let mut last_check = format!("{} in {:.2}s", result, duration.as_secs_f64());
+            if let Some(checked) = app.checked {
+                last_check += &format!(", {}", ago(checked.elapsed().unwrap_or_default()));
+            }
+            details.push(("last check", last_check));
+        }
+        if let Some(failure) = app.failure.as_ref().filter(|f| !f.stderr.is_empty()) {
+            details.push(("stderr", failure.stderr.clone()));
+        }
+        Ok(details)
+    }
+
+    fn history(&self, name: &str) -> Result<Vec<history::Entry>> {
+        let history = history::read(name)?;
+        if history.is_empty() && !self.apps.contains_key(name) {
+            return Err(format!("App `{}` is not registered.", name).into());
+        }
+        Ok(history)
+    }
+
+    fn synced(&self) -> &config::Sync {
+        &self.synced
+    }
+
+    fn export(&self, embed_scripts: bool) -> Result<Bundle> {
+        Bundle::new(&self.apps, embed_scripts)
+    }
+
+    fn import(
+        &mut self,
+        bundle: Bundle,
+        on_conflict: Conflict,
+    ) -> Result<Vec<(String, &'static str)>> {
+        let mut imported = vec![];
+        for (name, mut app) in bundle.apps()? {
+            let what = match self.apps.get_mut(&name) {
+                None => {
+                    bundle.install(&mut app)?;
+                    self.replace(&name, app, "imported");
+                    "added"
+                }
+                Some(ours) => match on_conflict {
+                    Conflict::Skip => "skipped, already registered",
+                    Conflict::Overwrite if ours.from_config => "skipped, declared in the config",
+                    Conflict::Overwrite => {
+                        bundle.install(&mut app)?;
+                        self.replace(&name, app, "imported");
+                        "overwritten"
+                    }
+                    Conflict::KeepNewer => {
+                        let newer = match ours.snapshot_value.as_str() {
+                            NONE => app.snapshot_value != NONE,
+                            snapshot => {
+                                ours.scheme.status(snapshot, &app.snapshot_value) == Status::Newer
+                            }
+                        };
+                        if newer {
+                            let note = Some("imported".to_owned());
+                            self.set_snapshot(&name, app.snapshot_value, false, note);
+                            "took the newer snapshot"
+                        } else {
+                            "skipped, our snapshot is as new"
+                        }
+                    }
+                },
+            };
+            imported.push((name, what));
+        }
+        Ok(imported)
     }
 }
 impl ActionsInternal for Ups {
     fn save(&self) -> Result<()> {
-        let mut data = std::fs::File::create(data_path()?)?;
-
-        for (name, app) in &self.apps {
-            writeln!(
-                data,
-                "{}\t{}\t{}\t{}\t",
-                name,
-                app.snapshot_value,
-                app.latest_value,
-                app.script_path.display()
-            )?;
-        }
-        Ok(())
+        data::save(&data_path()?, &self.apps, &self.base)?;
+        history::append(&self.history)
     }
 
     fn load(&mut self) -> Result<()>
     where
         Self: Sized,
     {
-        const PARSE_ERROR: &str = "Error while parsing data file";
         let data_path = data_path()?;
-        if !data_path.exists() {
-            return Ok(());
-        }
-
-        let data = std::fs::read_to_string(data_path)?;
-
-        let mut apps = HashMap::new();
-        for line in data.lines() {
-            let mut line = line.split_whitespace();
-            let name = line.next().ok_or(PARSE_ERROR)?;
-            let snapshot_value = line.next().ok_or(PARSE_ERROR)?;
-            let latest_value = line.next().ok_or(PARSE_ERROR)?;
-            let script_path = line.next().ok_or(PARSE_ERROR)?;
-            apps.insert(
-                name.into(),
-                App {
-                    script_path: script_path.into(),
-                    latest_value: latest_value.into(),
-                    snapshot_value: snapshot_value.into(),
-                },
-            );
+        let legacy_path = data_path.with_file_name(data::LEGACY_FILE);
+        if !data_path.exists() && legacy_path.exists() {
+            data::migrate(&legacy_path, &data_path)?;
         }
-        self.apps = apps;
-        Ok(())
+        if data_path.exists() {
+            (self.apps, self.base) = data::read(&data_path)?;
+        }
+        self.merge_config()
     }
 }
 
-fn data_path() -> Result<PathBuf> {
+/// A check detached from `Ups`, so it can run on another thread
+struct Job {
+    label: String,
+    checker: Checker,
+    exec: Exec,
+    /// Kept when the script says nothing changed
+    previous: Latest,
+    rules: Vec<Rule>,
+    timeout: Duration,
+    retries: u32,
+}
+
+impl Job {
+    fn run(self) -> Result<Outcome> {
+        // The clock starts when the check does, not while it waits for a worker
+        let start = Instant::now();
+        println!(
+            "{}",
+            format!("Fetching latest value of `{}` app...", self.label).yellow()
+        );
+        std::io::stdout().flush()?;
+
+        let mut attempt = 0;
+        let latest = loop {
+            // Every attempt gets the whole timeout
+            let deadline = Instant::now() + self.timeout;
+            match self.checker.fetch_latest(&self.exec, deadline) {
+                Err(failure) if failure.kind.is_retryable() && attempt < self.retries => {
+                    let delay = backoff(attempt);
+                    attempt += 1;
+                    println!(
+                        "{}",
+                        format!(
+                            "Retrying `{}` in {:.1}s ({}/{}): {}",
+                            self.label,
+                            delay.as_secs_f64(),
+                            attempt,
+                            self.retries,
+                            failure.status()
+                        )
+                        .yellow()
+                    );
+                    std::io::stdout().flush()?;
+                    std::thread::sleep(delay);
+                }
+                latest => break latest,
+            }
+        };
+        Ok(Outcome {
+            latest: latest.and_then(|mut latest| {
+                if latest.unchanged {
+                    // Nothing to keep, so there is no value to report
+                    if self.previous.value == NONE {
+                        return Err(Failure {
+                            exit_code: Some(UNCHANGED_EXIT_CODE),
+                            ..Failure::new(FailureKind::Exit)
+                                .stderr(b"Exited with `unchanged` but there is no previous value")
+                        });
+                    }
+                    return Ok(self.previous);
+                }
+                latest.value = normalize(&self.rules, &latest.raw);
+                Ok(latest)
+            }),
+            duration: start.elapsed(),
+        })
+    }
+}
+
+/// Exponential, with jitter so apps that failed together don't all retry at once
+fn backoff(attempt: u32) -> Duration {
+    let delay = RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_RETRY_DELAY);
+    delay.mul_f64(0.5 + fastrand::f64() / 2.0)
+}
+
+fn stale_after() -> Result<Duration> {
+    match std::env::var("UPS_STALE_AFTER") {
+        Ok(after) => Ok(humantime::parse_duration(&after).map_err(|_| {
+            format!(
+                "UPS_STALE_AFTER: expected a duration like `12h`, got `{}`",
+                after
+            )
+        })?),
+        Err(_) => Ok(DEFAULT_STALE_AFTER),
+    }
+}
+
+/// Rounded down to the largest unit, it only has to tell fresh from stale at a glance
+fn ago(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => "just now".to_owned(),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+fn max_jobs() -> Result<usize> {
+    match std::env::var("UPS_JOBS") {
+        Ok(jobs) => Ok(jobs
+            .parse()
+            .map_err(|_| format!("UPS_JOBS: expected a number, got `{}`", jobs))?),
+        Err(_) => Ok(DEFAULT_JOBS),
+    }
+}
+
+fn status_color(status: Status) -> ColorDesc {
+    match status {
+        Status::Same => ColorDesc::green(),
+        Status::Newer => ColorDesc::red(),
+        Status::Older => PURPLE_COLOR,
+        Status::Incomparable => ORANGE_COLOR,
+    }
+}
+
+/// Asks on the terminal, anything but yes is a no
+fn confirm(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// A single app's error is returned as is, for several each one is printed and the error
+/// counts them
+fn report(errors: Vec<String>, total: usize, what: &str) -> Result<()> {
+    match errors.as_slice() {
+        [] => Ok(()),
+        [error] if total == 1 => Err(error.clone().into()),
+        _ => {
+            for error in &errors {
+                eprintln!("{}", error.color(PINK_COLOR));
+            }
+            Err(format!("{} of {} {}", errors.len(), total, what).into())
+        }
+    }
+}
+
+/// Quotes `s` for a POSIX shell, if it needs it
+fn quote(s: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-+=./:@%,".contains(c);
+    if !s.is_empty() && s.chars().all(plain) {
+        s.to_owned()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
+fn status_name(status: Status) -> &'static str {
+    match status {
+        Status::Same => "up to date",
+        Status::Newer => "newer",
+        Status::Older => "older",
+        Status::Incomparable => "incomparable",
+    }
+}
+
+/// `UPS_CONFIG`, or else `apps.toml` in the config dir
+fn config_path() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os("UPS_CONFIG") {
+        return Ok(path.into());
+    }
+    Ok(dirs::config_dir()
+        .ok_or("Can not find xdg_config_dir")?
+        .join("ups")
+        .join("apps.toml"))
+}
+
+fn data_dir() -> Result<PathBuf> {
     let data_dir = dirs::data_dir()
         .ok_or("Can not find xdg_data_dir")?
         .join("ups");
@@ -255,24 +1340,61 @@ fn data_path() -> Result<PathBuf> {
             return Err(e.into());
         }
     }
-    Ok(data_dir.join("data"))
+    Ok(data_dir)
+}
+
+fn data_path() -> Result<PathBuf> {
+    Ok(data_dir()?.join("data.json"))
 }
 
 const fn usage() -> &'static str {
     "Ups: Check for app's updates
 
     - ups # Check for updates
+    - ups --expand # Check for updates, also listing the version in every known repository
+    - ups check [apps..] (--expand) # Check only these apps, the others keep their last values
+    - ups list (apps..) (--expand) # Show the last values without checking, with how long ago each app was
+      checked, older than `UPS_STALE_AFTER` (24h) is stale. `ups --no-fetch` is the same
+    - UPS_JOBS=[n] ups # Check at most n apps at once (8), `UPS_HOST_JOBS` caps the requests per host (4)
     - ups insert [app] [check_update_script_path] # Insert an app into ups
-    - ups snapshot [app] # Snapshot latest version
-    - ups get [app] # Show the latest version of the specified app
-    - ups show [app] # Show the script of the specified app"
-}
-
-trait Join<T> {
-    fn tawait(self) -> T;
-}
-impl<T> Join<T> for std::thread::JoinHandle<T> {
-    fn tawait(self) -> T {
-        self.join().expect("Thread panicked")
-    }
+    - ups insert [app] --github [owner/repo] (--url [api_url]) # Use the latest GitHub release or tag
+    - ups insert [app] --gitlab [group/project] (--url [instance_url]) # Use the latest GitLab release or tag
+    - ups insert [app] --gitea [owner/repo] --url [instance_url] # Same for Gitea and Forgejo (--forgejo)
+    - ups insert [app] --pypi [project] # Use the latest PyPI release
+    - ups insert [app] --crates [crate] # Use the latest crates.io release (--url for another sparse index)
+    - ups insert [app] --npm [package] # Use the latest npm release
+    - ups insert [app] --repology [project] # Use the newest version known to Repology
+    - ups insert [app] --git [url] (--tag [regex] | --branch [branch]) # Use the highest tag or a branch's commit
+    - ups insert [app] --http [url] (--header [header] | --header-env [name=VARIABLE]) [steps..] # Extract the value from a page, steps are
+      --regex [regex] --jsonpath [path] --css [selector(@attribute)] --first --last --sort
+    - ups insert [app] --aur [package] # Use the version in the AUR (--arch for the official repositories)
+    - ups insert [app] .. --packaged [script_path|provider:target] # Also show what is currently packaged (--no-packaged)
+    - ups insert [app] .. --scheme [semver|pep440|debian|calver|natural] # How versions are ordered (natural)
+    - ups insert [app] .. [rules..] # Normalize fetched values, rules are applied in order:
+      --strip-prefix [prefix] --strip-suffix [suffix] --replace [regex] [replacement]
+      --separator [from] [to] --lowercase
+    - ups insert [app] .. --timeout [seconds] # Give up on the check after that long, `UPS_TIMEOUT` sets the default (60)
+    - ups insert [app] .. --retries [n] # Retry failed checks with backoff, `UPS_RETRIES` sets the default (0)
+    - ups set [app] (--packaged ..) (--scheme ..) (rules..|--no-normalize) (--timeout ..|--no-timeout)
+      (--retries ..|--no-retries) # Change the options of an app
+    - ups packaged [app] [script_path|provider:target] # Set (or with no source, unset) the packaged source
+    - ups insert [app] --cmd [command] # Use the output of a shell command
+    - ups insert [app] .. --arg [arg] --env [KEY=value] --cwd [dir] # How the script or command runs, repeat
+      --arg and --env for more, clear them with --no-args --no-env --no-cwd
+    - ups insert [app] [script_path] --store # Keep a copy of the script, `ups set [app] --store` for an existing app
+    - ups insert [app] .. --tags [tag,..] # Tag the app to select it with @tag (--no-tags)
+    - ups edit [app] # Edit the app's script in $EDITOR, then check that it still works
+    - ups snapshot [apps..] (--note [message]) (--yes) # Snapshot latest version, logged in the history (`UPS_AUTHOR` or $USER),
+      asking first when several snapshots would change
+    - ups snapshot [apps..] --to [value] # Set the snapshot without running the check
+    - ups snapshot --undo [apps..] # Go back to the snapshot before the last one
+    - ups get [apps..] # Show the latest version of the specified apps
+    - ups show [apps..] # Show the settings, values and script of the specified apps
+    - ups remove [apps..] (--yes) # Remove apps, asking first when there are several
+      [apps..] are names, patterns like `python-*`, @tag or --all
+    - ups history [app] # Show every check and value change of the app, `UPS_HISTORY_DAYS` sets how long
+      checks are kept (90), `UPS_HISTORY_COMPACT=0` keeps repeated checks apart
+    - ups export (file) (--embed-scripts) # Write every app to a bundle, stdout by default
+    - ups import [file|-] (--on-conflict skip|overwrite|keep-newer) # Add the apps of a bundle (skip)
+    - ups sync # Add and remove the apps declared in apps.toml (`UPS_CONFIG` to use another file)"
 }
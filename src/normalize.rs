@@ -0,0 +1,171 @@
+use std::fmt;
+use std::str::FromStr;
+
+use regex::Regex;
+
+use crate::provider::decode;
+use crate::{Error, Result, NONE};
+
+/// Rewrites a fetched value into the form we package, e.g. `release-1_2` -> `1.2`
+#[derive(Debug, Clone)]
+pub enum Rule {
+    StripPrefix(String),
+    StripSuffix(String),
+    /// Regex and its replacement, which can refer to groups as `$1`
+    Replace(Regex, String),
+    /// Turns every occurrence of the first separator into the second
+    Separator(String, String),
+    Lowercase,
+}
+
+impl Rule {
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            Self::StripPrefix(prefix) => value
+                .strip_prefix(prefix.as_str())
+                .unwrap_or(value)
+                .to_owned(),
+            Self::StripSuffix(suffix) => value
+                .strip_suffix(suffix.as_str())
+                .unwrap_or(value)
+                .to_owned(),
+            Self::Replace(regex, replacement) => {
+                regex.replace_all(value, replacement.as_str()).into_owned()
+            }
+            Self::Separator(from, to) => value.replace(from.as_str(), to),
+            Self::Lowercase => value.to_lowercase(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::StripPrefix(_) => "strip-prefix",
+            Self::StripSuffix(_) => "strip-suffix",
+            Self::Replace(..) => "replace",
+            Self::Separator(..) => "separator",
+            Self::Lowercase => "lowercase",
+        }
+    }
+
+    fn args(&self) -> Vec<&str> {
+        match self {
+            Self::StripPrefix(prefix) => vec![prefix],
+            Self::StripSuffix(suffix) => vec![suffix],
+            Self::Replace(regex, replacement) => vec![regex.as_str(), replacement],
+            Self::Separator(from, to) => vec![from, to],
+            Self::Lowercase => vec![],
+        }
+    }
+
+    /// Number of values the `--<name>` flag takes
+    pub fn arity(name: &str) -> Option<usize> {
+        match name {
+            "strip-prefix" | "strip-suffix" => Some(1),
+            "replace" | "separator" => Some(2),
+            "lowercase" => Some(0),
+            _ => None,
+        }
+    }
+
+    pub fn new(name: &str, args: &[&str]) -> Result<Self> {
+        Ok(match (name, args) {
+            ("strip-prefix", [prefix]) => Self::StripPrefix((*prefix).to_owned()),
+            ("strip-suffix", [suffix]) => Self::StripSuffix((*suffix).to_owned()),
+            ("replace", [regex, replacement]) => {
+                Self::Replace(Regex::new(regex)?, (*replacement).to_owned())
+            }
+            ("separator", [from, to]) => Self::Separator((*from).to_owned(), (*to).to_owned()),
+            ("lowercase", []) => Self::Lowercase,
+            _ => return Err(format!("Invalid normalization rule `{}`", name).into()),
+        })
+    }
+}
+
+/// Applies the rules in order, `NONE` is left as is
+pub fn normalize(rules: &[Rule], value: &str) -> String {
+    if value == NONE {
+        return value.to_owned();
+    }
+    rules
+        .iter()
+        .fold(value.to_owned(), |value, rule| rule.apply(&value))
+}
+
+/// Written as `name:arg:arg`, with `%` and `:` escaped inside the arguments
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())?;
+        for arg in self.args() {
+            write!(f, ":{}", arg.replace('%', "%25").replace(':', "%3A"))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Rule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split(':');
+        let name = parts.next().unwrap_or_default();
+        let args = parts.map(decode).collect::<Result<Vec<_>>>()?;
+        Self::new(name, &args.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(s: &str) -> Rule {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        for s in [
+            "strip-prefix:v",
+            "strip-suffix:-final",
+            "replace:^release-(%5Cd+)_(%5Cd+)$:$1.$2",
+            "replace:(%5Cd)%3A(%5Cd):$1.$2",
+            "separator:_:.",
+            "separator:%25:%3A",
+            "lowercase",
+        ] {
+            let parsed = rule(s);
+            assert_eq!(rule(&parsed.to_string()).to_string(), parsed.to_string());
+        }
+        assert_eq!(rule("separator:%25:%3A").to_string(), "separator:%25:%3A");
+        assert_eq!(rule("separator:%25:%3A").apply("1%2"), "1:2");
+    }
+
+    #[test]
+    fn invalid() {
+        for s in [
+            "",
+            "unknown",
+            "lowercase:x",
+            "strip-prefix",
+            "replace:(:x",
+            "separator:%zz:.",
+        ] {
+            assert!(s.parse::<Rule>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn apply_in_order() {
+        let rules = [
+            rule("strip-prefix:Release-"),
+            rule("separator:_:."),
+            rule("lowercase"),
+        ];
+        assert_eq!(normalize(&rules, "Release-1_2_RC1"), "1.2.rc1");
+        assert_eq!(normalize(&rules, "release-1_2"), "release-1.2");
+        assert_eq!(normalize(&rules, NONE), NONE);
+        assert_eq!(
+            normalize(&[rule(r"replace:^v(\d+)-(\d+)$:$1.$2")], "v1-2"),
+            "1.2"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::checker::Checker;
+use crate::normalize::Rule;
+use crate::version::Scheme;
+use crate::Result;
+
+/// Per app settings given as `--flag value` to `insert` or `set`, `None` leaves a setting alone
+#[derive(Debug, Default)]
+pub struct Options {
+    /// Where the packaged version comes from, `Some(None)` stops tracking it
+    pub packaged: Option<Option<Checker>>,
+    pub scheme: Option<Scheme>,
+    /// Replaces all the rules at once, `--no-normalize` gives an empty list
+    pub normalize: Option<Vec<Rule>>,
+    /// `Some(None)` goes back to the global timeout
+    pub timeout: Option<Option<Duration>>,
+    /// `Some(None)` goes back to the global retries
+    pub retries: Option<Option<u32>>,
+    /// Copy the app's scripts into the store
+    pub store: bool,
+    /// Replace the whole list, like `normalize`
+    pub args: Option<Vec<String>>,
+    pub env: Option<BTreeMap<String, String>>,
+    pub cwd: Option<Option<PathBuf>>,
+    /// Replaces all the tags, `--no-tags` gives an empty list
+    pub tags: Option<Vec<String>>,
+}
+
+impl Options {
+    /// Takes the flags it knows out of `args`, the rest belongs to the checker
+    pub fn take<'a>(args: &[&'a str]) -> Result<(Self, Vec<&'a str>)> {
+        let mut options = Self::default();
+        let mut rest = vec![];
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .copied()
+                    .ok_or_else(|| format!("Missing value for `{}`", arg))
+            };
+            match *arg {
+                "--packaged" => options.packaged = Some(Some(Checker::from_arg(value()?)?)),
+                "--no-packaged" => options.packaged = Some(None),
+                "--scheme" => options.scheme = Some(value()?.parse()?),
+                "--no-normalize" => options.normalize = Some(vec![]),
+                "--timeout" => options.timeout = Some(Some(parse_seconds(value()?)?)),
+                "--no-timeout" => options.timeout = Some(None),
+                "--retries" => options.retries = Some(Some(parse_retries(value()?)?)),
+                "--no-retries" => options.retries = Some(None),
+                "--store" => options.store = true,
+                "--arg" => options
+                    .args
+                    .get_or_insert_with(Vec::new)
+                    .push(value()?.to_owned()),
+                "--no-args" => options.args = Some(vec![]),
+                "--env" => {
+                    let (key, val) = value()?
+                        .split_once('=')
+                        .ok_or_else(|| format!("Expected `KEY=value` after `{}`", arg))?;
+                    options
+                        .env
+                        .get_or_insert_with(BTreeMap::new)
+                        .insert(key.to_owned(), val.to_owned());
+                }
+                "--no-env" => options.env = Some(BTreeMap::new()),
+                "--cwd" => options.cwd = Some(Some(PathBuf::from(value()?).canonicalize()?)),
+                "--no-cwd" => options.cwd = Some(None),
+                "--tags" => options.tags = Some(crate::select::parse_tags(value()?)?),
+                "--no-tags" => options.tags = Some(vec![]),
+                flag => match flag
+                    .strip_prefix("--")
+                    .and_then(|name| Some((name, Rule::arity(name)?)))
+                {
+                    Some((name, arity)) => {
+                        let args = (0..arity)
+                            .map(|_| value())
+                            .collect::<std::result::Result<Vec<_>, _>>()?;
+                        options
+                            .normalize
+                            .get_or_insert_with(Vec::new)
+                            .push(Rule::new(name, &args)?);
+                    }
+                    None => rest.push(flag),
+                },
+            }
+        }
+        Ok((options, rest))
+    }
+}
+
+pub fn parse_seconds(s: &str) -> Result<Duration> {
+    s.parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("Expected a number of seconds, got `{}`", s).into())
+}
+
+pub fn parse_retries(s: &str) -> Result<u32> {
+    s.parse()
+        .map_err(|_| format!("Expected a number of retries, got `{}`", s).into())
+}
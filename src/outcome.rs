@@ -0,0 +1,157 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::provider::Latest;
+use crate::{Error, Result};
+
+/// How much of a failed script's stderr is kept
+const STDERR_TAIL_LINES: usize = 10;
+const STDERR_TAIL_CHARS: usize = 2000;
+
+/// What a single check produced and how long it took
+#[derive(Debug)]
+pub struct Outcome {
+    pub latest: std::result::Result<Latest, Failure>,
+    pub duration: Duration,
+}
+
+/// Why a check has no value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The script exited with a non-zero code or was killed by a signal
+    Exit,
+    /// The script succeeded but printed nothing
+    Empty,
+    /// The script printed something that isn't UTF-8
+    Encoding,
+    Timeout,
+    /// The script couldn't be started, e.g. it was moved or isn't executable
+    Spawn,
+    /// Upstream is unavailable for now, a 5xx, a 429 or a network error
+    Unavailable,
+    /// A built-in provider failed for good, e.g. a 404 or an unexpected response
+    Provider,
+}
+
+impl FailureKind {
+    pub const NAMES: &'static [&'static str] = &[
+        "exit",
+        "empty",
+        "encoding",
+        "timeout",
+        "spawn",
+        "unavailable",
+        "provider",
+    ];
+
+    /// Whether trying again might help, a script printing nothing will likely do it again
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::Exit | Self::Timeout | Self::Unavailable)
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Exit => "exit",
+            Self::Empty => "empty",
+            Self::Encoding => "encoding",
+            Self::Timeout => "timeout",
+            Self::Spawn => "spawn",
+            Self::Unavailable => "unavailable",
+            Self::Provider => "provider",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for FailureKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "exit" => Ok(Self::Exit),
+            "empty" => Ok(Self::Empty),
+            "encoding" => Ok(Self::Encoding),
+            "timeout" => Ok(Self::Timeout),
+            "spawn" => Ok(Self::Spawn),
+            "unavailable" => Ok(Self::Unavailable),
+            "provider" => Ok(Self::Provider),
+            _ => Err(format!(
+                "Unknown error kind `{}`, expected one of: {}",
+                s,
+                Self::NAMES.join(", ")
+            )
+            .into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub kind: FailureKind,
+    /// `None` when the script was killed by a signal, or wasn't a script
+    pub exit_code: Option<i32>,
+    /// The tail of the script's stderr, for providers and scripts that couldn't start
+    /// the error message instead
+    pub stderr: String,
+}
+
+impl Failure {
+    pub fn new(kind: FailureKind) -> Self {
+        Self {
+            kind,
+            exit_code: None,
+            stderr: String::new(),
+        }
+    }
+
+    pub fn stderr(mut self, stderr: &[u8]) -> Self {
+        self.stderr = tail(&String::from_utf8_lossy(stderr));
+        self
+    }
+
+    /// Short enough for the status column
+    pub fn status(&self) -> String {
+        match (self.kind, self.exit_code) {
+            (FailureKind::Exit, Some(code)) => format!("error: exit {}", code),
+            (FailureKind::Exit, None) => "error: killed".to_owned(),
+            (FailureKind::Empty, _) => "error: empty".to_owned(),
+            (FailureKind::Encoding, _) => "error: not utf-8".to_owned(),
+            (FailureKind::Timeout, _) => "timeout".to_owned(),
+            (FailureKind::Spawn, _) => "error: spawn".to_owned(),
+            (FailureKind::Unavailable, _) => "error: unavailable".to_owned(),
+            (FailureKind::Provider, _) => "error: provider".to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.kind, self.exit_code) {
+            (FailureKind::Exit, Some(code)) => write!(f, "Exited with code {}", code)?,
+            (FailureKind::Exit, None) => f.write_str("Killed by a signal")?,
+            (FailureKind::Empty, _) => f.write_str("Printed nothing")?,
+            (FailureKind::Encoding, _) => f.write_str("Printed invalid UTF-8")?,
+            (FailureKind::Timeout, _) => f.write_str("Timed out")?,
+            (FailureKind::Spawn, _) => f.write_str("Could not start")?,
+            (FailureKind::Unavailable, _) => f.write_str("Upstream unavailable")?,
+            (FailureKind::Provider, _) => f.write_str("Provider failed")?,
+        }
+        if !self.stderr.is_empty() {
+            write!(f, ":\n{}", self.stderr)?;
+        }
+        Ok(())
+    }
+}
+
+/// The last lines of `s`, what usually says why a script failed
+fn tail(s: &str) -> String {
+    let lines: Vec<_> = s.trim_end().lines().collect();
+    let tail = lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n");
+    match tail.char_indices().rev().nth(STDERR_TAIL_CHARS) {
+        Some((i, _)) => tail[i..].to_owned(),
+        None => tail,
+    }
+}
@@ -0,0 +1,31 @@
+use std::sync::Mutex;
+
+/// Runs `f` on every item with at most `jobs` of them in flight, results keep the items' order
+pub fn map<T, R, F>(jobs: usize, items: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let len = items.len();
+    let queue = Mutex::new(items.into_iter().enumerate());
+    let results = Mutex::new((0..len).map(|_| None).collect::<Vec<Option<R>>>());
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, len.max(1)) {
+            scope.spawn(|| loop {
+                let next = queue.lock().expect("Worker panicked").next();
+                let Some((i, item)) = next else { break };
+                let result = f(item);
+                results.lock().expect("Worker panicked")[i] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .expect("Worker panicked")
+        .into_iter()
+        .map(|result| result.expect("Every item was processed"))
+        .collect()
+}
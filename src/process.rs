@@ -0,0 +1,151 @@
+use std::io::Read;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use crate::{Result, TimedOut};
+
+/// Like `Command::output`, but once `deadline` passes the child is killed together with
+/// everything it started (a script's curl for example) and `TimedOut` is returned
+pub fn output(command: &mut Command, deadline: Instant) -> Result<Output> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // Its own process group, so the whole tree can be killed at once
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+
+    let mut child = command.spawn()?;
+    // Read both pipes while waiting, a child blocked on a full pipe never exits
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            kill(&mut child);
+            return Err(TimedOut.into());
+        }
+        std::thread::sleep((deadline - now).min(Duration::from_millis(20)));
+    };
+
+    // Something it left in the background may still hold the pipes open, `sleep 60 &`
+    let mut read = |pipe: Receiver<Vec<u8>>| {
+        let output = pipe.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+        if output.is_err() {
+            kill(&mut child);
+        }
+        output.map_err(|_| TimedOut)
+    };
+    Ok(Output {
+        status,
+        stdout: read(stdout)?,
+        stderr: read(stderr)?,
+    })
+}
+
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut buf = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        let _ = sender.send(buf);
+    });
+    receiver
+}
+
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    // SAFETY: plain syscall, the negative pid targets the group `output` created
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    #[cfg(not(unix))]
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `script` with a one second deadline, it writes the pids to watch to `$1`
+    fn run(name: &str, script: &str) -> (Result<Output>, Duration, Vec<u32>) {
+        let pids =
+            std::env::temp_dir().join(format!("ups-process-{}-{}", name, std::process::id()));
+        let start = Instant::now();
+        let result = output(
+            Command::new("sh")
+                .arg("-c")
+                .arg(script)
+                .arg("sh")
+                .arg(&pids),
+            start + Duration::from_secs(1),
+        );
+        let elapsed = start.elapsed();
+        let written = std::fs::read_to_string(&pids).unwrap_or_default();
+        let _ = std::fs::remove_file(pids);
+        let pids = written.lines().map(|pid| pid.parse().unwrap()).collect();
+        (result, elapsed, pids)
+    }
+
+    /// Gone, or a zombie nobody reaped yet
+    #[cfg(target_os = "linux")]
+    fn is_dead(pid: u32) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        loop {
+            let dead = match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+                Ok(stat) => stat
+                    .rsplit(')')
+                    .next()
+                    .unwrap_or_default()
+                    .trim_start()
+                    .starts_with('Z'),
+                Err(_) => true,
+            };
+            if dead || Instant::now() >= deadline {
+                return dead;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn output_and_status() {
+        let (result, _, _) = run("output", "echo out; echo err >&2; exit 3");
+        let output = result.unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+
+    #[test]
+    fn hung_group_is_killed() {
+        let (result, elapsed, pids) = run(
+            "hung",
+            r#"echo $$ > "$1"; sleep 60 & echo $! >> "$1"; sleep 60"#,
+        );
+        assert!(result.unwrap_err().is::<TimedOut>());
+        assert!(elapsed < Duration::from_secs(3), "{:?}", elapsed);
+        assert_eq!(pids.len(), 2);
+        #[cfg(target_os = "linux")]
+        for pid in pids {
+            assert!(is_dead(pid), "{} is still running", pid);
+        }
+    }
+
+    #[test]
+    fn background_child_holding_the_pipes() {
+        let (result, elapsed, pids) = run("background", r#"sleep 60 & echo $! > "$1"; echo 1"#);
+        assert!(result.unwrap_err().is::<TimedOut>());
+        assert!(elapsed < Duration::from_secs(3), "{:?}", elapsed);
+        #[cfg(target_os = "linux")]
+        assert!(is_dead(pids[0]), "{} is still running", pids[0]);
+    }
+}
@@ -0,0 +1,61 @@
+use std::time::Instant;
+
+use serde::Deserialize;
+
+use super::{Fetch, Spec};
+use crate::{http, Result};
+
+const URL: &str = "https://archlinux.org";
+
+/// Version of a package in the official Arch Linux repositories, stable repositories win
+/// over testing ones
+#[derive(Debug, Clone)]
+pub struct Arch {
+    pub package: String,
+    /// Website base url, defaults to archlinux.org
+    pub url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Search {
+    results: Vec<Package>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    pkgver: String,
+    repo: String,
+}
+
+impl Arch {
+    pub const KIND: &'static str = "arch";
+
+    pub fn from_spec(spec: Spec) -> Result<Self> {
+        spec.check_options(&["url"])?;
+        Ok(Self {
+            url: spec.get("url").map(ToOwned::to_owned),
+            package: spec.target,
+        })
+    }
+
+    pub fn to_spec(&self) -> Spec {
+        Spec::new(Self::KIND, &self.package).option("url", self.url.as_deref())
+    }
+}
+
+impl Fetch for Arch {
+    fn fetch(&self, deadline: Instant) -> Result<String> {
+        let base = self.url.as_deref().unwrap_or(URL).trim_end_matches('/');
+        let search: Search = http::get_json(
+            &format!("{}/packages/search/json/?name={}", base, self.package),
+            &[],
+            deadline,
+        )?;
+        search
+            .results
+            .into_iter()
+            .min_by_key(|package| package.repo.ends_with("testing"))
+            .map(|package| package.pkgver)
+            .ok_or_else(|| format!("`{}` is not in the Arch repositories", self.package).into())
+    }
+}
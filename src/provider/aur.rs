@@ -0,0 +1,66 @@
+use std::time::Instant;
+
+use serde::Deserialize;
+
+use super::{Fetch, Spec};
+use crate::{http, Result};
+
+const URL: &str = "https://aur.archlinux.org";
+
+/// Version of an AUR package, through the RPC interface
+#[derive(Debug, Clone)]
+pub struct Aur {
+    pub package: String,
+    /// AUR base url, defaults to aur.archlinux.org
+    pub url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Info {
+    results: Vec<Package>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Package {
+    version: String,
+}
+
+impl Aur {
+    pub const KIND: &'static str = "aur";
+
+    pub fn from_spec(spec: Spec) -> Result<Self> {
+        spec.check_options(&["url"])?;
+        Ok(Self {
+            url: spec.get("url").map(ToOwned::to_owned),
+            package: spec.target,
+        })
+    }
+
+    pub fn to_spec(&self) -> Spec {
+        Spec::new(Self::KIND, &self.package).option("url", self.url.as_deref())
+    }
+}
+
+impl Fetch for Aur {
+    fn fetch(&self, deadline: Instant) -> Result<String> {
+        let base = self.url.as_deref().unwrap_or(URL).trim_end_matches('/');
+        let info: Info = http::get_json(
+            &format!("{}/rpc/v5/info?arg[]={}", base, self.package),
+            &[],
+            deadline,
+        )?;
+        let package = info
+            .results
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("`{}` is not in the AUR", self.package))?;
+        Ok(pkgver(&package.version).to_owned())
+    }
+}
+
+/// Strips the epoch and pkgrel from `epoch:pkgver-pkgrel`, leaving what upstream calls the version
+fn pkgver(version: &str) -> &str {
+    let version = version.split_once(':').map_or(version, |(_, v)| v);
+    version.rsplit_once('-').map_or(version, |(v, _)| v)
+}
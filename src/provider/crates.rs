@@ -0,0 +1,127 @@
+use std::time::Instant;
+
+use serde::Deserialize;
+
+use super::{Fetch, Spec};
+use crate::version::{is_semver_prerelease, natural_cmp};
+use crate::{http, Result};
+
+const URL: &str = "https://index.crates.io";
+
+/// Latest stable, non-yanked version of a crate, read from a sparse registry index
+#[derive(Debug, Clone)]
+pub struct Crates {
+    pub name: String,
+    /// Sparse index base url, defaults to index.crates.io
+    pub url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IndexEntry {
+    vers: String,
+    #[serde(default)]
+    yanked: bool,
+}
+
+impl Crates {
+    pub const KIND: &'static str = "crates";
+
+    pub fn from_spec(spec: Spec) -> Result<Self> {
+        spec.check_options(&["url"])?;
+        // Crate names are ASCII, which also keeps `index_path` on char boundaries
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        if spec.target.is_empty() || !spec.target.chars().all(valid) {
+            return Err(format!("Invalid crate name `{}`", spec.target).into());
+        }
+        Ok(Self {
+            url: spec.get("url").map(ToOwned::to_owned),
+            name: spec.target,
+        })
+    }
+
+    pub fn to_spec(&self) -> Spec {
+        Spec::new(Self::KIND, &self.name).option("url", self.url.as_deref())
+    }
+
+    /// Index files are sharded by the first characters of the lowercased name
+    fn index_path(&self) -> String {
+        let name = self.name.to_lowercase();
+        match name.len() {
+            1 => format!("1/{}", name),
+            2 => format!("2/{}", name),
+            3 => format!("3/{}/{}", &name[..1], name),
+            _ => format!("{}/{}/{}", &name[..2], &name[2..4], name),
+        }
+    }
+}
+
+impl Fetch for Crates {
+    fn fetch(&self, deadline: Instant) -> Result<String> {
+        let base = self.url.as_deref().unwrap_or(URL);
+        // Cargo writes sparse registries as `sparse+https://..`
+        let base = base.strip_prefix("sparse+").unwrap_or(base);
+        let index = http::get_text(
+            &format!("{}/{}", base.trim_end_matches('/'), self.index_path()),
+            &[],
+            deadline,
+        )?;
+
+        let mut latest: Option<String> = None;
+        for line in index.lines().filter(|line| !line.trim().is_empty()) {
+            let entry: IndexEntry = serde_json::from_str(line)?;
+            if entry.yanked || is_semver_prerelease(&entry.vers) {
+                continue;
+            }
+            if latest
+                .as_deref()
+                .is_none_or(|latest| natural_cmp(&entry.vers, latest).is_gt())
+            {
+                latest = Some(entry.vers);
+            }
+        }
+        latest.ok_or_else(|| format!("`{}` has no stable release", self.name).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::provider::tests::serve;
+
+    fn crates(name: &str, url: &str) -> Result<Crates> {
+        Crates::from_spec(Spec::new(Crates::KIND, name).option("url", Some(url)))
+    }
+
+    #[test]
+    fn skips_prereleases_and_yanked() {
+        let url = serve(|path| {
+            (path == "/se/rd/serde").then(|| {
+                [
+                    r#"{"name": "serde", "vers": "1.0.9"}"#,
+                    r#"{"name": "serde", "vers": "1.0.10"}"#,
+                    r#"{"name": "serde", "vers": "1.0.11", "yanked": true}"#,
+                    r#"{"name": "serde", "vers": "2.0.0-alpha.1"}"#,
+                    "",
+                ]
+                .join("\n")
+            })
+        });
+        let latest = crates("Serde", &format!("sparse+{}/", url))
+            .unwrap()
+            .fetch(Instant::now() + Duration::from_secs(10));
+        assert_eq!(latest.unwrap(), "1.0.10");
+    }
+
+    #[test]
+    fn index_paths() {
+        let path = |name| crates(name, "http://localhost").unwrap().index_path();
+        assert_eq!(path("a"), "1/a");
+        assert_eq!(path("ab"), "2/ab");
+        assert_eq!(path("abc"), "3/a/abc");
+        assert_eq!(path("Cargo_Edit"), "ca/rg/cargo_edit");
+        assert!(crates("", "http://localhost").is_err());
+        assert!(crates("café", "http://localhost").is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use std::process::Command;
+use std::time::Instant;
+
+use regex::Regex;
+
+use super::{Fetch, Spec};
+use crate::version::natural_cmp;
+use crate::{process, Result, Unavailable};
+
+/// Reads a repository's refs with `git ls-remote`, so anything git can clone works,
+/// including local bare repositories
+#[derive(Debug, Clone)]
+pub struct Git {
+    /// Anything `git ls-remote` accepts: https, ssh or a local path
+    pub url: String,
+    pub track: Track,
+}
+
+#[derive(Debug, Clone)]
+pub enum Track {
+    /// Highest tag, optionally only among the ones matching the regex
+    Tag(Option<Regex>),
+    /// Commit hash at the head of the branch
+    Branch(String),
+}
+
+impl Git {
+    pub const KIND: &'static str = "git";
+
+    pub fn from_spec(spec: Spec) -> Result<Self> {
+        spec.check_options(&["tag", "branch"])?;
+        let track = match (spec.get("tag"), spec.get("branch")) {
+            (Some(_), Some(_)) => return Err("`git` takes either `tag` or `branch`".into()),
+            (Some(pattern), None) => Track::Tag(Some(Regex::new(pattern)?)),
+            (None, Some(branch)) => Track::Branch(branch.to_owned()),
+            (None, None) => Track::Tag(None),
+        };
+        Ok(Self {
+            url: spec.target,
+            track,
+        })
+    }
+
+    pub fn to_spec(&self) -> Spec {
+        let spec = Spec::new(Self::KIND, &self.url);
+        match &self.track {
+            Track::Tag(pattern) => spec.option("tag", pattern.as_ref().map(Regex::as_str)),
+            Track::Branch(branch) => spec.option("branch", Some(branch)),
+        }
+    }
+
+    /// `(hash, ref)` pairs
+    fn ls_remote(&self, args: &[&str], deadline: Instant) -> Result<Vec<(String, String)>> {
+        let output = process::output(
+            Command::new("git")
+                .arg("ls-remote")
+                .args(args)
+                .arg("--")
+                .arg(&self.url)
+                // Fail instead of waiting for credentials nobody will type
+                .env("GIT_TERMINAL_PROMPT", "0"),
+            deadline,
+        )?;
+        // git exits with 128 for about everything, only its message tells a network error
+        // from a missing repository or a refused login
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = format!("git ls-remote {}: {}", self.url, stderr.trim());
+            return Err(match is_transient(&stderr) {
+                true => Unavailable(message).into(),
+                false => message.into(),
+            });
+        }
+        Ok(String::from_utf8(output.stdout)?
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(hash, name)| (hash.to_owned(), name.to_owned()))
+            .collect())
+    }
+}
+
+/// Messages of failures that may go away on their own, from git, curl and ssh
+const TRANSIENT_ERRORS: &[&str] = &[
+    "could not resolve host",
+    "could not resolve hostname",
+    "temporary failure in name resolution",
+    "failed to connect",
+    "connection refused",
+    "connection reset",
+    "connection timed out",
+    "operation timed out",
+    "network is unreachable",
+    "the remote end hung up unexpectedly",
+    "unexpected disconnect",
+    "early eof",
+    "rpc failed",
+    "gnutls",
+    "ssl_",
+    "tls connection",
+];
+
+fn is_transient(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    if TRANSIENT_ERRORS.iter().any(|error| stderr.contains(error)) {
+        return true;
+    }
+    // HTTP errors, `The requested URL returned error: 503`
+    stderr
+        .split("returned error: ")
+        .skip(1)
+        .any(|rest| rest.starts_with('5') || rest.starts_with("429"))
+}
+
+impl Fetch for Git {
+    fn fetch(&self, deadline: Instant) -> Result<String> {
+        match &self.track {
+            Track::Tag(pattern) => self
+                .ls_remote(&["--tags", "--refs"], deadline)?
+                .into_iter()
+                .filter_map(|(_, name)| name.strip_prefix("refs/tags/").map(ToOwned::to_owned))
+                .filter(|tag| pattern.as_ref().is_none_or(|pattern| pattern.is_match(tag)))
+                .max_by(|a, b| natural_cmp(a, b))
+                .ok_or_else(|| format!("No matching tag in `{}`", self.url).into()),
+            Track::Branch(branch) => {
+                let head = format!("refs/heads/{}", branch);
+                self.ls_remote(&["--heads"], deadline)?
+                    .into_iter()
+                    .find(|(_, name)| *name == head)
+                    .map(|(hash, _)| hash)
+                    .ok_or_else(|| format!("No branch `{}` in `{}`", branch, self.url).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(["-c", "user.name=ups", "-c", "user.email=ups@localhost"])
+            .args(["-c", "init.defaultBranch=main", "-c", "tag.gpgSign=false"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?}: {:?}", args, output);
+        String::from_utf8(output.stdout).unwrap().trim().to_owned()
+    }
+
+    /// A bare repository with tags `v1.9`, `v1.10`, `nightly` and branches `main` and `dev`
+    fn repository(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("ups-git-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let (bare, work) = (root.join("bare.git"), root.join("work"));
+        std::fs::create_dir_all(&work).unwrap();
+        git(&root, &["init", "--bare", "bare.git"]);
+        git(&work, &["init"]);
+        for tag in ["v1.9", "v1.10", "nightly"] {
+            git(&work, &["commit", "--allow-empty", "-m", tag]);
+            git(&work, &["tag", "-a", tag, "-m", tag]);
+        }
+        git(&work, &["branch", "dev", "HEAD~1"]);
+        git(
+            &work,
+            &["push", "--tags", bare.to_str().unwrap(), "main", "dev"],
+        );
+        bare
+    }
+
+    fn fetch(bare: &Path, options: &[(&str, &str)]) -> Result<String> {
+        let mut spec = Spec::new(Git::KIND, bare.to_str().unwrap());
+        for (key, value) in options {
+            spec = spec.option(key, Some(value));
+        }
+        Git::from_spec(spec)?.fetch(Instant::now() + Duration::from_secs(10))
+    }
+
+    #[test]
+    fn tags() {
+        let bare = repository("tags");
+        assert_eq!(fetch(&bare, &[]).unwrap(), "v1.10");
+        assert_eq!(fetch(&bare, &[("tag", r"^v\d+\.\d+$")]).unwrap(), "v1.10");
+        assert_eq!(fetch(&bare, &[("tag", "^n")]).unwrap(), "nightly");
+        let error = fetch(&bare, &[("tag", "^release-")]).unwrap_err();
+        assert!(
+            error.to_string().starts_with("No matching tag"),
+            "{}",
+            error
+        );
+        std::fs::remove_dir_all(bare.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn branches() {
+        let bare = repository("branches");
+        let work = bare.parent().unwrap().join("work");
+        assert_eq!(
+            fetch(&bare, &[("branch", "main")]).unwrap(),
+            git(&work, &["rev-parse", "main"])
+        );
+        assert_eq!(
+            fetch(&bare, &[("branch", "dev")]).unwrap(),
+            git(&work, &["rev-parse", "dev"])
+        );
+        let error = fetch(&bare, &[("branch", "gone")]).unwrap_err();
+        assert!(
+            error.to_string().starts_with("No branch `gone`"),
+            "{}",
+            error
+        );
+        std::fs::remove_dir_all(bare.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn missing_repository_is_permanent() {
+        let missing = std::env::temp_dir().join(format!("ups-git-missing-{}", std::process::id()));
+        let error = fetch(&missing, &[]).unwrap_err();
+        assert!(!error.is::<Unavailable>(), "{}", error);
+        assert!(is_transient(
+            "fatal: unable to access 'x': The requested URL returned error: 503"
+        ));
+        assert!(!is_transient(
+            "fatal: unable to access 'x': The requested URL returned error: 403"
+        ));
+    }
+}
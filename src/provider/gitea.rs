@@ -0,0 +1,149 @@
+use std::time::Instant;
+
+use serde::Deserialize;
+
+use super::{find_in_pages, owner_repo, Fetch, Spec};
+use crate::{http, Result};
+
+/// Gitea caps page sizes at 50 by default
+const PER_PAGE: usize = 50;
+
+/// Latest release of a Gitea or Forgejo repository, falling back to its tags
+///
+/// There is no canonical instance so `url` is required, set `GITEA_TOKEN` for private repositories
+#[derive(Debug, Clone)]
+pub struct Gitea {
+    /// `owner/repo`
+    pub repo: String,
+    /// Instance base url, e.g. https://codeberg.org
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    draft: bool,
+}
+
+#[derive(Deserialize)]
+struct Tag {
+    name: String,
+}
+
+impl Gitea {
+    pub const KIND: &'static str = "gitea";
+    /// Forgejo is a Gitea fork with the same API
+    pub const FORGEJO_KIND: &'static str = "forgejo";
+
+    pub fn from_spec(spec: Spec) -> Result<Self> {
+        spec.check_options(&["url"])?;
+        Ok(Self {
+            repo: owner_repo(&spec)?,
+            url: spec
+                .get("url")
+                .ok_or_else(|| format!("`{}` needs an instance `url`", spec.kind))?
+                .to_owned(),
+        })
+    }
+
+    pub fn to_spec(&self) -> Spec {
+        Spec::new(Self::KIND, &self.repo).option("url", Some(&self.url))
+    }
+
+    fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        page: usize,
+        deadline: Instant,
+    ) -> Result<T> {
+        let mut headers = vec![];
+        if let Ok(token) = std::env::var("GITEA_TOKEN") {
+            headers.push(("Authorization", format!("token {}", token)));
+        }
+        http::get_json(
+            &format!(
+                "{}/api/v1/repos/{}/{}?limit={}&page={}",
+                self.url.trim_end_matches('/'),
+                self.repo,
+                path,
+                PER_PAGE,
+                page
+            ),
+            &headers,
+            deadline,
+        )
+    }
+}
+
+impl Fetch for Gitea {
+    fn fetch(&self, deadline: Instant) -> Result<String> {
+        let release = find_in_pages(
+            PER_PAGE,
+            |page| self.get::<Vec<Release>>("releases", page, deadline),
+            |release| (!release.draft).then(|| release.tag_name.clone()),
+        )?;
+        if let Some(release) = release {
+            return Ok(release);
+        }
+
+        let tags: Vec<Tag> = self.get("tags", 1, deadline)?;
+        tags.into_iter()
+            .next()
+            .map(|tag| tag.name)
+            .ok_or_else(|| format!("`{}` has no releases or tags", self.repo).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::provider::tests::serve;
+
+    fn fetch(url: &str) -> Result<String> {
+        let gitea = Gitea::from_spec(
+            Spec::new(Gitea::FORGEJO_KIND, "owner/repo").option("url", Some(url)),
+        )?;
+        gitea.fetch(Instant::now() + Duration::from_secs(10))
+    }
+
+    #[test]
+    fn skips_drafts_across_pages() {
+        let url = serve(|path| {
+            let releases: Vec<_> = match path {
+                "/api/v1/repos/owner/repo/releases?limit=50&page=1" => {
+                    vec![("v2.0", true); PER_PAGE]
+                }
+                "/api/v1/repos/owner/repo/releases?limit=50&page=2" => {
+                    vec![("v1.1", false), ("v1.0", false)]
+                }
+                _ => return None,
+            };
+            let releases: Vec<_> = releases
+                .into_iter()
+                .map(|(tag, draft)| serde_json::json!({"tag_name": tag, "draft": draft}))
+                .collect();
+            Some(serde_json::to_string(&releases).unwrap())
+        });
+        assert_eq!(fetch(&url).unwrap(), "v1.1");
+    }
+
+    #[test]
+    fn falls_back_to_tags() {
+        let url = serve(|path| match path {
+            "/api/v1/repos/owner/repo/releases?limit=50&page=1" => Some("[]".to_owned()),
+            "/api/v1/repos/owner/repo/tags?limit=50&page=1" => {
+                Some(r#"[{"name": "v0.2"}, {"name": "v0.1"}]"#.to_owned())
+            }
+            _ => None,
+        });
+        assert_eq!(fetch(&url).unwrap(), "v0.2");
+    }
+
+    #[test]
+    fn needs_an_url() {
+        let error = Gitea::from_spec(Spec::new(Gitea::KIND, "owner/repo")).unwrap_err();
+        assert_eq!(error.to_string(), "`gitea` needs an instance `url`");
+    }
+}
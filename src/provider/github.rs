@@ -0,0 +1,193 @@
+use std::time::Instant;
+
+use serde::Deserialize;
+
+use super::{find_in_pages, owner_repo, Fetch, Spec};
+use crate::version::natural_cmp;
+use crate::{http, Result};
+
+const API_URL: &str = "https://api.github.com";
+const PER_PAGE: usize = 100;
+
+/// Latest final release of a GitHub repository, falling back to its highest tag when it has
+/// no releases
+///
+/// Set `GITHUB_TOKEN` to raise the API rate limit
+#[derive(Debug, Clone)]
+pub struct GitHub {
+    /// `owner/repo`
+    pub repo: String,
+    /// API base url, defaults to api.github.com
+    pub url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+}
+
+#[derive(Deserialize)]
+struct Tag {
+    name: String,
+}
+
+impl GitHub {
+    pub const KIND: &'static str = "github";
+
+    pub fn from_spec(spec: Spec) -> Result<Self> {
+        spec.check_options(&["url"])?;
+        Ok(Self {
+            repo: owner_repo(&spec)?,
+            url: spec.get("url").map(ToOwned::to_owned),
+        })
+    }
+
+    pub fn to_spec(&self) -> Spec {
+        Spec::new(Self::KIND, &self.repo).option("url", self.url.as_deref())
+    }
+
+    fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        page: usize,
+        deadline: Instant,
+    ) -> Result<T> {
+        let base = self.url.as_deref().unwrap_or(API_URL).trim_end_matches('/');
+        let mut headers = vec![
+            ("Accept", "application/vnd.github+json".to_owned()),
+            ("X-GitHub-Api-Version", "2022-11-28".to_owned()),
+        ];
+        if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+            headers.push(("Authorization", format!("Bearer {}", token)));
+        }
+        http::get_json(
+            &format!(
+                "{}/repos/{}/{}?per_page={}&page={}",
+                base, self.repo, path, PER_PAGE, page
+            ),
+            &headers,
+            deadline,
+        )
+    }
+}
+
+impl Fetch for GitHub {
+    fn fetch(&self, deadline: Instant) -> Result<String> {
+        // Releases come newest first, but drafts (visible with a token) and pre-releases
+        // can fill whole pages
+        let release = find_in_pages(
+            PER_PAGE,
+            |page| self.get::<Vec<Release>>("releases", page, deadline),
+            |release| (!release.draft && !release.prerelease).then(|| release.tag_name.clone()),
+        )?;
+        if let Some(release) = release {
+            return Ok(release);
+        }
+
+        // Tags come in ref name order, `v9.0` before `v10.0`, so all of them are needed
+        let mut tags = vec![];
+        for page in 1.. {
+            let page: Vec<Tag> = self.get("tags", page, deadline)?;
+            let last = page.len() < PER_PAGE;
+            tags.extend(page.into_iter().map(|tag| tag.name));
+            if last {
+                break;
+            }
+        }
+        tags.into_iter()
+            .max_by(|a, b| natural_cmp(a, b))
+            .ok_or_else(|| format!("`{}` has no releases or tags", self.repo).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::provider::tests::serve;
+
+    fn fetch(url: &str) -> Result<String> {
+        let github =
+            GitHub::from_spec(Spec::new(GitHub::KIND, "owner/repo").option("url", Some(url)))?;
+        github.fetch(Instant::now() + Duration::from_secs(10))
+    }
+
+    fn releases(releases: &[(&str, bool)]) -> String {
+        let releases: Vec<_> = releases
+            .iter()
+            .map(|(tag, draft)| {
+                serde_json::json!({"tag_name": tag, "draft": draft, "prerelease": false})
+            })
+            .collect();
+        serde_json::to_string(&releases).unwrap()
+    }
+
+    #[test]
+    fn skips_drafts_across_pages() {
+        let url = serve(|path| match path {
+            "/repos/owner/repo/releases?per_page=100&page=1" => {
+                Some(releases(&[("v3.0-draft", true); PER_PAGE]))
+            }
+            "/repos/owner/repo/releases?per_page=100&page=2" => Some(releases(&[
+                ("v2.1-draft", true),
+                ("v2.0", false),
+                ("v1.0", false),
+            ])),
+            _ => None,
+        });
+        assert_eq!(fetch(&url).unwrap(), "v2.0");
+    }
+
+    #[test]
+    fn falls_back_to_tags() {
+        let url = serve(|path| match path {
+            "/repos/owner/repo/releases?per_page=100&page=1" => {
+                Some(releases(&[("v2.0-draft", true)]))
+            }
+            // Ref name order, the highest version is on the second page
+            "/repos/owner/repo/tags?per_page=100&page=1" => {
+                let tags: Vec<_> = (0..PER_PAGE)
+                    .map(|i| serde_json::json!({"name": format!("v9.{}", i)}))
+                    .collect();
+                Some(serde_json::to_string(&tags).unwrap())
+            }
+            "/repos/owner/repo/tags?per_page=100&page=2" => {
+                Some(r#"[{"name": "v10.0"}, {"name": "v1.0"}]"#.to_owned())
+            }
+            _ => None,
+        });
+        assert_eq!(fetch(&url).unwrap(), "v10.0");
+    }
+
+    #[test]
+    fn skips_prereleases() {
+        let url = serve(|path| {
+            (path == "/repos/owner/repo/releases?per_page=100&page=1").then(|| {
+                r#"[
+                    {"tag_name": "v2.0-rc.1", "draft": false, "prerelease": true},
+                    {"tag_name": "v1.9", "draft": false, "prerelease": false}
+                ]"#
+                .to_owned()
+            })
+        });
+        assert_eq!(fetch(&url).unwrap(), "v1.9");
+    }
+
+    #[test]
+    fn no_releases_or_tags() {
+        let url = serve(|path| {
+            path.starts_with("/repos/owner/repo/")
+                .then(|| "[]".to_owned())
+        });
+        let error = fetch(&url).unwrap_err().to_string();
+        assert!(error.contains("has no releases or tags"), "{}", error);
+
+        let url = serve(|_| None);
+        let error = fetch(&format!("{}/", url)).unwrap_err().to_string();
+        assert!(error.contains("404"), "{}", error);
+    }
+}
@@ -0,0 +1,153 @@
+use std::time::Instant;
+
+use serde::Deserialize;
+
+use super::{find_in_pages, Fetch, Spec};
+use crate::{http, Result};
+
+const URL: &str = "https://gitlab.com";
+const PER_PAGE: usize = 100;
+
+/// Latest release of a GitLab project, falling back to its most recently updated tag
+///
+/// Works with any self-hosted instance through `url`, set `GITLAB_TOKEN` for private projects
+#[derive(Debug, Clone)]
+pub struct GitLab {
+    /// Full project path, `group/subgroup/project`
+    pub project: String,
+    /// Instance base url, defaults to gitlab.com
+    pub url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    upcoming_release: bool,
+}
+
+#[derive(Deserialize)]
+struct Tag {
+    name: String,
+}
+
+impl GitLab {
+    pub const KIND: &'static str = "gitlab";
+
+    pub fn from_spec(spec: Spec) -> Result<Self> {
+        spec.check_options(&["url"])?;
+        if !spec.target.contains('/') {
+            return Err(format!("Expected `group/project`, got `{}`", spec.target).into());
+        }
+        Ok(Self {
+            url: spec.get("url").map(ToOwned::to_owned),
+            project: spec.target,
+        })
+    }
+
+    pub fn to_spec(&self) -> Spec {
+        Spec::new(Self::KIND, &self.project).option("url", self.url.as_deref())
+    }
+
+    fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &str,
+        deadline: Instant,
+    ) -> Result<T> {
+        let base = self.url.as_deref().unwrap_or(URL).trim_end_matches('/');
+        let mut headers = vec![];
+        if let Ok(token) = std::env::var("GITLAB_TOKEN") {
+            headers.push(("PRIVATE-TOKEN", token));
+        }
+        http::get_json(
+            &format!(
+                "{}/api/v4/projects/{}/{}?{}",
+                base,
+                self.project.replace('/', "%2F"),
+                path,
+                query
+            ),
+            &headers,
+            deadline,
+        )
+    }
+}
+
+impl Fetch for GitLab {
+    fn fetch(&self, deadline: Instant) -> Result<String> {
+        // Sorted by release date, newest first
+        let release = find_in_pages(
+            PER_PAGE,
+            |page| {
+                self.get::<Vec<Release>>(
+                    "releases",
+                    &format!("per_page={}&page={}", PER_PAGE, page),
+                    deadline,
+                )
+            },
+            |release| (!release.upcoming_release).then(|| release.tag_name.clone()),
+        )?;
+        if let Some(release) = release {
+            return Ok(release);
+        }
+
+        let tags: Vec<Tag> = self.get(
+            "repository/tags",
+            "order_by=updated&sort=desc&per_page=1",
+            deadline,
+        )?;
+        tags.into_iter()
+            .next()
+            .map(|tag| tag.name)
+            .ok_or_else(|| format!("`{}` has no releases or tags", self.project).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::provider::tests::serve;
+
+    fn fetch(url: &str) -> Result<String> {
+        let gitlab = GitLab::from_spec(
+            Spec::new(GitLab::KIND, "group/sub/project").option("url", Some(url)),
+        )?;
+        gitlab.fetch(Instant::now() + Duration::from_secs(10))
+    }
+
+    #[test]
+    fn skips_upcoming_releases() {
+        let url = serve(|path| match path {
+            "/api/v4/projects/group%2Fsub%2Fproject/releases?per_page=100&page=1" => Some(
+                r#"[{"tag_name": "v2.0", "upcoming_release": true}, {"tag_name": "v1.0"}]"#
+                    .to_owned(),
+            ),
+            _ => None,
+        });
+        assert_eq!(fetch(&url).unwrap(), "v1.0");
+    }
+
+    #[test]
+    fn falls_back_to_last_updated_tag() {
+        let url = serve(|path| {
+            match path {
+            "/api/v4/projects/group%2Fsub%2Fproject/releases?per_page=100&page=1" => {
+                Some("[]".to_owned())
+            }
+            "/api/v4/projects/group%2Fsub%2Fproject/repository/tags?order_by=updated&sort=desc&per_page=1" => {
+                Some(r#"[{"name": "v0.9"}]"#.to_owned())
+            }
+            _ => None,
+        }
+        });
+        assert_eq!(fetch(&url).unwrap(), "v0.9");
+    }
+
+    #[test]
+    fn needs_a_group() {
+        assert!(GitLab::from_spec(Spec::new(GitLab::KIND, "project")).is_err());
+    }
+}
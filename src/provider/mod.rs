@@ -0,0 +1,388 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Instant;
+
+use crate::{Error, Result};
+
+mod arch;
+mod aur;
+mod crates;
+mod git;
+mod gitea;
+mod github;
+mod gitlab;
+mod npm;
+mod pipeline;
+mod pypi;
+mod repology;
+
+pub use arch::Arch;
+pub use aur::Aur;
+pub use crates::Crates;
+pub use git::Git;
+pub use gitea::Gitea;
+pub use github::GitHub;
+pub use gitlab::GitLab;
+pub use npm::Npm;
+pub use pipeline::{Pipeline, Step};
+pub use pypi::PyPi;
+pub use repology::{RepoVersion, Repology};
+
+/// Something that knows how to find the latest version of an app
+pub trait Fetch {
+    fn fetch(&self, deadline: Instant) -> Result<String>;
+}
+
+/// The latest value of an app and, for providers that know them, its versions in
+/// other repositories
+#[derive(Debug, Clone, Default)]
+pub struct Latest {
+    pub value: String,
+    /// The value as fetched, before the app's normalization rules
+    pub raw: String,
+    pub repos: Vec<RepoVersion>,
+    /// The script said nothing changed since the previous check, which is kept
+    pub unchanged: bool,
+}
+
+impl From<String> for Latest {
+    fn from(value: String) -> Self {
+        Self {
+            raw: value.clone(),
+            value,
+            repos: vec![],
+            unchanged: false,
+        }
+    }
+}
+
+/// Built-in providers, they query upstream natively instead of running a script
+///
+/// A provider is written as a spec string `kind:target?option=value&..`, this is how it
+/// is stored in the data file and shown in the table
+#[derive(Debug, Clone)]
+pub enum Provider {
+    GitHub(GitHub),
+    GitLab(GitLab),
+    Gitea(Gitea),
+    PyPi(PyPi),
+    Crates(Crates),
+    Npm(Npm),
+    Aur(Aur),
+    Arch(Arch),
+    Repology(Repology),
+    Git(Git),
+    Pipeline(Pipeline),
+}
+
+impl Provider {
+    pub const KINDS: &'static [&'static str] = &[
+        GitHub::KIND,
+        GitLab::KIND,
+        Gitea::KIND,
+        Gitea::FORGEJO_KIND,
+        PyPi::KIND,
+        Crates::KIND,
+        Npm::KIND,
+        Aur::KIND,
+        Arch::KIND,
+        Repology::KIND,
+        Git::KIND,
+        Pipeline::KIND,
+    ];
+
+    pub fn is_kind(kind: &str) -> bool {
+        Self::KINDS.contains(&kind)
+    }
+
+    pub fn from_spec(spec: Spec) -> Result<Self> {
+        match spec.kind.as_str() {
+            GitHub::KIND => Ok(Self::GitHub(GitHub::from_spec(spec)?)),
+            GitLab::KIND => Ok(Self::GitLab(GitLab::from_spec(spec)?)),
+            Gitea::KIND | Gitea::FORGEJO_KIND => Ok(Self::Gitea(Gitea::from_spec(spec)?)),
+            PyPi::KIND => Ok(Self::PyPi(PyPi::from_spec(spec)?)),
+            Crates::KIND => Ok(Self::Crates(Crates::from_spec(spec)?)),
+            Npm::KIND => Ok(Self::Npm(Npm::from_spec(spec)?)),
+            Aur::KIND => Ok(Self::Aur(Aur::from_spec(spec)?)),
+            Arch::KIND => Ok(Self::Arch(Arch::from_spec(spec)?)),
+            Repology::KIND => Ok(Self::Repology(Repology::from_spec(spec)?)),
+            Git::KIND => Ok(Self::Git(Git::from_spec(spec)?)),
+            Pipeline::KIND => Ok(Self::Pipeline(Pipeline::from_spec(spec)?)),
+            kind => Err(format!("Unknown provider `{}`", kind).into()),
+        }
+    }
+
+    pub fn to_spec(&self) -> Spec {
+        match self {
+            Self::GitHub(p) => p.to_spec(),
+            Self::GitLab(p) => p.to_spec(),
+            Self::Gitea(p) => p.to_spec(),
+            Self::PyPi(p) => p.to_spec(),
+            Self::Crates(p) => p.to_spec(),
+            Self::Npm(p) => p.to_spec(),
+            Self::Aur(p) => p.to_spec(),
+            Self::Arch(p) => p.to_spec(),
+            Self::Repology(p) => p.to_spec(),
+            Self::Git(p) => p.to_spec(),
+            Self::Pipeline(p) => p.to_spec(),
+        }
+    }
+
+    /// Like `fetch`, but keeps the repository versions of the providers that have them
+    pub fn fetch_latest(&self, deadline: Instant) -> Result<Latest> {
+        match self {
+            Self::Repology(p) => p.fetch_latest(deadline),
+            p => Ok(p.fetch(deadline)?.into()),
+        }
+    }
+}
+
+impl Fetch for Provider {
+    fn fetch(&self, deadline: Instant) -> Result<String> {
+        match self {
+            Self::GitHub(p) => p.fetch(deadline),
+            Self::GitLab(p) => p.fetch(deadline),
+            Self::Gitea(p) => p.fetch(deadline),
+            Self::PyPi(p) => p.fetch(deadline),
+            Self::Crates(p) => p.fetch(deadline),
+            Self::Npm(p) => p.fetch(deadline),
+            Self::Aur(p) => p.fetch(deadline),
+            Self::Arch(p) => p.fetch(deadline),
+            Self::Repology(p) => p.fetch(deadline),
+            Self::Git(p) => p.fetch(deadline),
+            Self::Pipeline(p) => p.fetch(deadline),
+        }
+    }
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_spec().fmt(f)
+    }
+}
+
+impl FromStr for Provider {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_spec(s.parse()?)
+    }
+}
+
+/// Walks a paginated listing, newest first, until `pick` accepts an entry or the pages run out
+fn find_in_pages<T, R>(
+    per_page: usize,
+    mut get_page: impl FnMut(usize) -> Result<Vec<T>>,
+    pick: impl Fn(&T) -> Option<R>,
+) -> Result<Option<R>> {
+    for page in 1.. {
+        let entries = get_page(page)?;
+        if let Some(found) = entries.iter().find_map(&pick) {
+            return Ok(Some(found));
+        }
+        if entries.len() < per_page {
+            break;
+        }
+    }
+    Ok(None)
+}
+
+/// Returns `spec.target` if it looks like `owner/repo`
+fn owner_repo(spec: &Spec) -> Result<String> {
+    match spec.target.split('/').collect::<Vec<_>>().as_slice() {
+        [owner, repo] if !owner.is_empty() && !repo.is_empty() => Ok(spec.target.clone()),
+        _ => Err(format!("Expected `owner/repo`, got `{}`", spec.target).into()),
+    }
+}
+
+/// The parsed form of a provider spec string
+#[derive(Debug, Clone, Default)]
+pub struct Spec {
+    pub kind: String,
+    pub target: String,
+    pub options: Vec<(String, String)>,
+}
+
+impl Spec {
+    pub fn new(kind: &str, target: &str) -> Self {
+        Self {
+            kind: kind.to_owned(),
+            target: target.to_owned(),
+            options: vec![],
+        }
+    }
+
+    pub fn option(mut self, key: &str, value: Option<&str>) -> Self {
+        if let Some(value) = value {
+            self.options.push((key.to_owned(), value.to_owned()));
+        }
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Fails on options the provider doesn't know about, so typos don't go unnoticed
+    pub fn check_options(&self, known: &[&str]) -> Result<()> {
+        match self
+            .options
+            .iter()
+            .find(|(k, _)| !known.contains(&k.as_str()))
+        {
+            Some((key, _)) => Err(format!("`{}` does not support `{}`", self.kind, key).into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Spec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind, encode(&self.target))?;
+        for (i, (key, value)) in self.options.iter().enumerate() {
+            let sep = if i == 0 { '?' } else { '&' };
+            write!(f, "{}{}={}", sep, encode(key), encode(value))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Spec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, rest) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid provider spec `{}`", s))?;
+        let (target, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let mut options = vec![];
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid option `{}` in provider spec `{}`", pair, s))?;
+            options.push((decode(key)?, decode(value)?));
+        }
+        Ok(Self {
+            kind: kind.to_owned(),
+            target: decode(target)?,
+            options,
+        })
+    }
+}
+
+/// Percent-encodes the characters that have a meaning inside a spec string,
+/// plus whitespace so a spec is always a single word
+pub fn encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '%' | '?' | '&' | '=' | '#' => encoded.push_str(&format!("%{:02X}", c as u32)),
+            c if c.is_ascii_whitespace() => encoded.push_str(&format!("%{:02X}", c as u32)),
+            c => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+pub fn decode(s: &str) -> Result<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex: Vec<u8> = iter.by_ref().take(2).collect();
+            let hex = std::str::from_utf8(&hex)?;
+            bytes.push(
+                u8::from_str_radix(hex, 16)
+                    .map_err(|_| format!("Invalid escape `%{}` in `{}`", hex, s))?,
+            );
+        } else {
+            bytes.push(b);
+        }
+    }
+    Ok(String::from_utf8(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    use super::*;
+
+    /// Serves `respond(path_and_query)` as JSON on a local port and returns the base url,
+    /// `None` is a 404
+    pub(super) fn serve(respond: impl Fn(&str) -> Option<String> + Send + 'static) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                // Skip the headers, requests have no body
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let path = request.split(' ').nth(1).unwrap_or_default();
+                let (status, body) = match respond(path) {
+                    Some(body) => ("200 OK", body),
+                    None => ("404 Not Found", "{}".to_owned()),
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn spec_round_trip() {
+        let spec = Spec::new("gitlab", "group/sub project")
+            .option("url", Some("https://git.example.com/?a=b&c=%d#e"));
+        let encoded = spec.to_string();
+        assert_eq!(
+            encoded,
+            "gitlab:group/sub%20project?url=https://git.example.com/%3Fa%3Db%26c%3D%25d%23e"
+        );
+        let decoded: Spec = encoded.parse().unwrap();
+        assert_eq!(decoded.kind, "gitlab");
+        assert_eq!(decoded.target, "group/sub project");
+        assert_eq!(decoded.options, spec.options);
+        assert_eq!(decoded.to_string(), encoded);
+    }
+
+    #[test]
+    fn spec_parse() {
+        let spec: Spec = "github:owner/repo?url=http://localhost:8080&x=1"
+            .parse()
+            .unwrap();
+        assert_eq!(spec.get("url"), Some("http://localhost:8080"));
+        assert_eq!(spec.get("x"), Some("1"));
+        assert!(spec.check_options(&["url"]).is_err());
+        assert_eq!(decode("a%3Ab%e2%9c%93").unwrap(), "a:b✓");
+
+        assert!("github".parse::<Spec>().is_err());
+        assert!("github:a/b?url".parse::<Spec>().is_err());
+        assert!("github:a%zz".parse::<Spec>().is_err());
+        assert!("github:a%".parse::<Spec>().is_err());
+    }
+
+    #[test]
+    fn provider_from_spec() {
+        let provider: Provider = "github:owner/repo".parse().unwrap();
+        assert_eq!(provider.to_string(), "github:owner/repo");
+        assert!("github:owner".parse::<Provider>().is_err());
+        assert!("github:owner/repo?branch=x".parse::<Provider>().is_err());
+        assert!("nope:owner/repo".parse::<Provider>().is_err());
+    }
+}
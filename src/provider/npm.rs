@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+
+use super::{Fetch, Spec};
+use crate::version::{is_semver_prerelease, natural_cmp};
+use crate::{http, Result};
+
+const URL: &str = "https://registry.npmjs.org";
+
+/// Version tagged `latest` on the npm registry, or the highest stable one if that tag
+/// points to a pre-release
+#[derive(Debug, Clone)]
+pub struct Npm {
+    /// Package name, scoped packages are written `@scope/name`
+    pub package: String,
+    /// Registry base url, defaults to registry.npmjs.org
+    pub url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    #[serde(rename = "dist-tags", default)]
+    dist_tags: HashMap<String, String>,
+    #[serde(default)]
+    versions: HashMap<String, IgnoredAny>,
+}
+
+impl Npm {
+    pub const KIND: &'static str = "npm";
+
+    pub fn from_spec(spec: Spec) -> Result<Self> {
+        spec.check_options(&["url"])?;
+        Ok(Self {
+            url: spec.get("url").map(ToOwned::to_owned),
+            package: spec.target,
+        })
+    }
+
+    pub fn to_spec(&self) -> Spec {
+        Spec::new(Self::KIND, &self.package).option("url", self.url.as_deref())
+    }
+}
+
+impl Fetch for Npm {
+    fn fetch(&self, deadline: Instant) -> Result<String> {
+        let base = self.url.as_deref().unwrap_or(URL).trim_end_matches('/');
+        // The abbreviated document is much smaller and has all we need
+        let package: Package = http::get_json(
+            &format!("{}/{}", base, self.package.replace('/', "%2F")),
+            &[("Accept", "application/vnd.npm.install-v1+json".to_owned())],
+            deadline,
+        )?;
+
+        if let Some(latest) = package.dist_tags.get("latest") {
+            if !is_semver_prerelease(latest) {
+                return Ok(latest.clone());
+            }
+        }
+        package
+            .versions
+            .into_keys()
+            .filter(|version| !is_semver_prerelease(version))
+            .max_by(|a, b| natural_cmp(a, b))
+            .ok_or_else(|| format!("`{}` has no stable release", self.package).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::provider::tests::serve;
+
+    fn fetch(package: &'static str) -> Result<String> {
+        let url = serve(move |path| (path == "/@scope%2Fname").then(|| package.to_owned()));
+        let npm = Npm::from_spec(Spec::new(Npm::KIND, "@scope/name").option("url", Some(&url)))?;
+        npm.fetch(Instant::now() + Duration::from_secs(10))
+    }
+
+    #[test]
+    fn latest_tag() {
+        let package = r#"{
+            "dist-tags": {"latest": "1.2.0", "next": "2.0.0-rc.1"},
+            "versions": {"1.2.0": {}, "1.3.0": {}, "2.0.0-rc.1": {}}
+        }"#;
+        assert_eq!(fetch(package).unwrap(), "1.2.0");
+    }
+
+    #[test]
+    fn highest_stable_when_latest_is_a_prerelease() {
+        let package = r#"{
+            "dist-tags": {"latest": "2.0.0-rc.1"},
+            "versions": {"1.9.0": {}, "1.10.0": {}, "2.0.0-rc.1": {}}
+        }"#;
+        assert_eq!(fetch(package).unwrap(), "1.10.0");
+
+        let package = r#"{"versions": {"0.1.0": {}, "0.2.0-beta": {}}}"#;
+        assert_eq!(fetch(package).unwrap(), "0.1.0");
+
+        let package = r#"{"dist-tags": {"latest": "1.0.0-rc.1"}, "versions": {}}"#;
+        let error = fetch(package).unwrap_err();
+        assert_eq!(error.to_string(), "`@scope/name` has no stable release");
+    }
+}
@@ -0,0 +1,309 @@
+use std::time::Instant;
+
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde_json_path::JsonPath;
+
+use super::{Fetch, Spec};
+use crate::version::natural_cmp;
+use crate::{http, Result};
+
+/// Fetches a url and runs the body through a chain of extraction steps, the declarative
+/// version of `curl | rg | head -n 1`
+///
+/// Every step maps a list of values to a new one, the first value left at the end wins
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    pub url: String,
+    /// `(name, value)` pairs sent with the request
+    pub headers: Vec<(String, String)>,
+    /// `(name, variable)` pairs, headers whose value is read from the environment when
+    /// fetching, so tokens stay out of the data file and the table
+    pub header_env: Vec<(String, String)>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Every match, or its first capture group when the regex has one
+    Regex(Regex),
+    /// Every node the path selects, strings unquoted
+    JsonPath(String, JsonPath),
+    /// Text of every element the selector matches, or one of their attributes when the
+    /// selector ends with `@attribute`
+    Css(String),
+    First,
+    Last,
+    /// Ascending version order, usually followed by `last`
+    Sort,
+}
+
+impl Step {
+    /// Keys of the steps in a spec, the ones without a value are given as `--key` flags
+    pub const KEYS: &'static [&'static str] =
+        &["regex", "jsonpath", "css", "first", "last", "sort"];
+    pub const FLAGS: &'static [&'static str] = &["first", "last", "sort"];
+
+    fn parse(key: &str, value: &str) -> Result<Self> {
+        Ok(match key {
+            "regex" => Self::Regex(Regex::new(value)?),
+            "jsonpath" => Self::JsonPath(value.to_owned(), JsonPath::parse(value)?),
+            "css" => {
+                let (selector, _) = split_attribute(value);
+                Selector::parse(selector)
+                    .map_err(|e| format!("Invalid selector `{}`: {}", value, e))?;
+                Self::Css(value.to_owned())
+            }
+            "first" => Self::First,
+            "last" => Self::Last,
+            "sort" => Self::Sort,
+            _ => return Err(format!("Unknown step `{}`", key).into()),
+        })
+    }
+
+    fn to_option(&self) -> (&'static str, &str) {
+        match self {
+            Self::Regex(regex) => ("regex", regex.as_str()),
+            Self::JsonPath(path, _) => ("jsonpath", path),
+            Self::Css(selector) => ("css", selector),
+            Self::First => ("first", ""),
+            Self::Last => ("last", ""),
+            Self::Sort => ("sort", ""),
+        }
+    }
+
+    fn apply(&self, values: Vec<String>) -> Result<Vec<String>> {
+        Ok(match self {
+            Self::Regex(regex) => values
+                .iter()
+                .flat_map(|value| regex.captures_iter(value))
+                .filter_map(|captures| captures.get(1).or_else(|| captures.get(0)))
+                .map(|m| m.as_str().to_owned())
+                .collect(),
+            Self::JsonPath(_, path) => {
+                let mut selected = vec![];
+                for value in values {
+                    let json: serde_json::Value = serde_json::from_str(&value)?;
+                    selected.extend(path.query(&json).all().into_iter().map(|node| match node {
+                        serde_json::Value::String(s) => s.clone(),
+                        node => node.to_string(),
+                    }));
+                }
+                selected
+            }
+            Self::Css(selector) => {
+                let (selector, attribute) = split_attribute(selector);
+                let selector = Selector::parse(selector)
+                    .map_err(|e| format!("Invalid selector `{}`: {}", selector, e))?;
+                let mut selected = vec![];
+                for value in values {
+                    let html = Html::parse_document(&value);
+                    selected.extend(
+                        html.select(&selector)
+                            .filter_map(|element| match attribute {
+                                Some(attribute) => element.attr(attribute).map(ToOwned::to_owned),
+                                None => Some(element.text().collect::<String>().trim().to_owned()),
+                            }),
+                    );
+                }
+                selected
+            }
+            Self::First => values.into_iter().take(1).collect(),
+            Self::Last => values.into_iter().last().into_iter().collect(),
+            Self::Sort => {
+                let mut values = values;
+                values.sort_by(|a, b| natural_cmp(a, b));
+                values
+            }
+        })
+    }
+}
+
+/// `a.release@href` -> (`a.release`, Some(`href`))
+fn split_attribute(selector: &str) -> (&str, Option<&str>) {
+    match selector.rsplit_once('@') {
+        Some((selector, attribute)) if !attribute.is_empty() && !attribute.contains(' ') => {
+            (selector, Some(attribute))
+        }
+        _ => (selector, None),
+    }
+}
+
+impl Pipeline {
+    pub const KIND: &'static str = "http";
+
+    pub fn from_spec(spec: Spec) -> Result<Self> {
+        let mut headers = vec![];
+        let mut header_env = vec![];
+        let mut steps = vec![];
+        for (key, value) in &spec.options {
+            if key == "header" {
+                let (name, value) = value
+                    .split_once(':')
+                    .ok_or_else(|| format!("Expected `name: value` header, got `{}`", value))?;
+                headers.push((name.trim().to_owned(), value.trim().to_owned()));
+            } else if key == "header-env" {
+                let (name, variable) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("Expected `name=VARIABLE`, got `{}`", value))?;
+                header_env.push((name.trim().to_owned(), variable.trim().to_owned()));
+            } else if Step::KEYS.contains(&key.as_str()) {
+                steps.push(Step::parse(key, value)?);
+            } else {
+                return Err(format!("`{}` does not support `{}`", spec.kind, key).into());
+            }
+        }
+        Ok(Self {
+            url: spec.target,
+            headers,
+            header_env,
+            steps,
+        })
+    }
+
+    pub fn to_spec(&self) -> Spec {
+        let mut spec = Spec::new(Self::KIND, &self.url);
+        for (name, value) in &self.headers {
+            spec = spec.option("header", Some(&format!("{}: {}", name, value)));
+        }
+        for (name, variable) in &self.header_env {
+            spec = spec.option("header-env", Some(&format!("{}={}", name, variable)));
+        }
+        for step in &self.steps {
+            let (key, value) = step.to_option();
+            spec = spec.option(key, Some(value));
+        }
+        spec
+    }
+}
+
+impl Fetch for Pipeline {
+    fn fetch(&self, deadline: Instant) -> Result<String> {
+        let mut headers: Vec<_> = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.clone()))
+            .collect();
+        for (name, variable) in &self.header_env {
+            let value = std::env::var(variable).map_err(|_| {
+                format!("`{}` is not set, it holds the `{}` header", variable, name)
+            })?;
+            headers.push((name.as_str(), value));
+        }
+        let mut values = vec![http::get_text(&self.url, &headers, deadline)?];
+        for step in &self.steps {
+            values = step.apply(values)?;
+        }
+        values
+            .into_iter()
+            .map(|value| value.trim().to_owned())
+            .find(|value| !value.is_empty())
+            .ok_or_else(|| format!("Nothing extracted from {}", self.url).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::provider::tests::serve;
+
+    fn pipeline(spec: &str) -> Pipeline {
+        Pipeline::from_spec(spec.parse().unwrap()).unwrap()
+    }
+
+    fn run(spec: &str, body: &str) -> Vec<String> {
+        pipeline(spec)
+            .steps
+            .iter()
+            .try_fold(vec![body.to_owned()], |values, step| step.apply(values))
+            .unwrap()
+    }
+
+    #[test]
+    fn regex() {
+        let body = r#"<a href="/tag/v1.2">v1.2</a> <a href="/tag/v1.10">"#;
+        assert_eq!(run("http:x?regex=tag/(v[^\"]*)", body), ["v1.2", "v1.10"]);
+        assert_eq!(run(r"http:x?regex=v\d+", body), ["v1", "v1", "v1"]);
+        assert_eq!(
+            run("http:x?regex=tag/(v[^\"]*)&sort=&last=", body),
+            ["v1.10"]
+        );
+        assert_eq!(run("http:x?regex=tag/(v[^\"]*)&first=", body), ["v1.2"]);
+    }
+
+    #[test]
+    fn jsonpath() {
+        let body = r#"{"versions": [{"num": "1.0", "n": 1}, {"num": "2.0", "n": 2}]}"#;
+        assert_eq!(
+            run("http:x?jsonpath=$.versions[*].num", body),
+            ["1.0", "2.0"]
+        );
+        assert_eq!(run("http:x?jsonpath=$.versions[*].n&last=", body), ["2"]);
+        assert!(Pipeline::from_spec("http:x?jsonpath=versions[".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn css() {
+        let body = r#"<ul>
+            <li><a class="release" href="/v2.0"> Version 2.0 </a></li>
+            <li><a class="release" href="/v1.0">Version 1.0</a></li>
+            <li><a href="/docs">Docs</a></li>
+        </ul>"#;
+        assert_eq!(
+            run("http:x?css=a.release", body),
+            ["Version 2.0", "Version 1.0"]
+        );
+        assert_eq!(run("http:x?css=a.release@href", body), ["/v2.0", "/v1.0"]);
+        assert_eq!(
+            run("http:x?css=li a@href&regex=/v(.*)&sort=&first=", body),
+            ["1.0"]
+        );
+        assert!(Pipeline::from_spec("http:x?css=a[".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn spec_round_trip() {
+        let spec = "http:https://example.com/releases?header=Accept%3A text/html&header-env=Authorization%3DMY_TOKEN&css=a@href&sort=&last=";
+        let pipeline = pipeline(spec);
+        assert_eq!(
+            pipeline.headers,
+            [("Accept".to_owned(), "text/html".to_owned())]
+        );
+        assert_eq!(
+            pipeline.header_env,
+            [("Authorization".to_owned(), "MY_TOKEN".to_owned())]
+        );
+        // The spec is shown in the table, only the variable's name is in it
+        assert_eq!(
+            pipeline.to_spec().to_string(),
+            "http:https://example.com/releases?header=Accept:%20text/html&header-env=Authorization%3DMY_TOKEN&css=a@href&sort=&last="
+        );
+        assert!(Pipeline::from_spec("http:x?header-env=Authorization".parse().unwrap()).is_err());
+        assert!(Pipeline::from_spec("http:x?header=Authorization".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn fetch() {
+        let url = serve(|path| (path == "/releases").then(|| r#"["1.9", "1.10", ""]"#.to_owned()));
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let spec = format!("http:{}/releases?jsonpath=$[*]&sort=&last=", url);
+        assert_eq!(pipeline(&spec).fetch(deadline).unwrap(), "1.10");
+
+        let spec = format!("http:{}/releases?regex=nothing", url);
+        let error = pipeline(&spec).fetch(deadline).unwrap_err().to_string();
+        assert!(error.starts_with("Nothing extracted"), "{}", error);
+
+        let spec = format!(
+            "http:{}/releases?header-env=Authorization%3DUPS_TEST_UNSET_TOKEN",
+            url
+        );
+        let error = pipeline(&spec).fetch(deadline).unwrap_err().to_string();
+        assert!(
+            error.contains("`UPS_TEST_UNSET_TOKEN` is not set"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use serde::Deserialize;
+
+use super::{Fetch, Spec};
+use crate::version::{is_pep440_prerelease, Scheme};
+use crate::{http, Result};
+
+const URL: &str = "https://pypi.org";
+
+/// Latest final, non-yanked release of a PyPI project
+#[derive(Debug, Clone)]
+pub struct PyPi {
+    pub project: String,
+    /// Index base url, defaults to pypi.org
+    pub url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Project {
+    releases: HashMap<String, Vec<File>>,
+}
+
+#[derive(Deserialize)]
+struct File {
+    #[serde(default)]
+    yanked: bool,
+}
+
+impl PyPi {
+    pub const KIND: &'static str = "pypi";
+
+    pub fn from_spec(spec: Spec) -> Result<Self> {
+        spec.check_options(&["url"])?;
+        Ok(Self {
+            url: spec.get("url").map(ToOwned::to_owned),
+            project: spec.target,
+        })
+    }
+
+    pub fn to_spec(&self) -> Spec {
+        Spec::new(Self::KIND, &self.project).option("url", self.url.as_deref())
+    }
+}
+
+impl Fetch for PyPi {
+    fn fetch(&self, deadline: Instant) -> Result<String> {
+        let base = self.url.as_deref().unwrap_or(URL).trim_end_matches('/');
+        let project: Project = http::get_json(
+            &format!("{}/pypi/{}/json", base, self.project),
+            &[],
+            deadline,
+        )?;
+
+        // A release is yanked when all of its files are, releases without files can't be installed.
+        // Versions that aren't PEP 440 predate it and can't be ordered, they are left out
+        project
+            .releases
+            .into_iter()
+            .filter(|(version, files)| {
+                !is_pep440_prerelease(version)
+                    && Scheme::Pep440.compare(version, version).is_some()
+                    && files.iter().any(|file| !file.yanked)
+            })
+            .map(|(version, _)| version)
+            .max_by(|a, b| {
+                Scheme::Pep440
+                    .compare(a, b)
+                    .expect("Both versions were parsed")
+            })
+            .ok_or_else(|| format!("`{}` has no final release", self.project).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::provider::tests::serve;
+
+    fn fetch(releases: &'static str) -> Result<String> {
+        let url = serve(move |path| {
+            (path == "/pypi/project/json").then(|| format!(r#"{{"releases": {}}}"#, releases))
+        });
+        let pypi = PyPi::from_spec(Spec::new(PyPi::KIND, "project").option("url", Some(&url)))?;
+        pypi.fetch(Instant::now() + Duration::from_secs(10))
+    }
+
+    #[test]
+    fn post_release_of_an_older_line() {
+        let releases = r#"{
+            "1.0": [{"yanked": false}],
+            "1.0.post1": [{"yanked": false}],
+            "1.0.1": [{"yanked": false}]
+        }"#;
+        assert_eq!(fetch(releases).unwrap(), "1.0.1");
+    }
+
+    #[test]
+    fn skips_prereleases_yanked_and_empty() {
+        let releases = r#"{
+            "1.9": [{"yanked": false}],
+            "1.10": [{"yanked": true}, {"yanked": false}],
+            "1.11": [{"yanked": true}],
+            "1.12": [],
+            "2.0rc1": [{"yanked": false}],
+            "2.0.dev3": [{"yanked": false}],
+            "not-a-version": [{"yanked": false}]
+        }"#;
+        assert_eq!(fetch(releases).unwrap(), "1.10");
+    }
+
+    #[test]
+    fn no_final_release() {
+        let error = fetch(r#"{"1.0a1": [{"yanked": false}]}"#).unwrap_err();
+        assert_eq!(error.to_string(), "`project` has no final release");
+    }
+}
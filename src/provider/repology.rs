@@ -0,0 +1,181 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Fetch, Latest, Spec};
+use crate::version::natural_cmp;
+use crate::{http, Error, Result};
+
+const URL: &str = "https://repology.org";
+
+/// Versions of a project across every repository Repology knows about, the latest value
+/// being the one Repology considers newest
+#[derive(Debug, Clone)]
+pub struct Repology {
+    /// Repology project name, usually the upstream name lowercased
+    pub project: String,
+    /// Repology base url, defaults to repology.org
+    pub url: Option<String>,
+}
+
+/// Version of a project in one repository
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoVersion {
+    pub repo: String,
+    pub version: String,
+    /// Repology status: newest, outdated, legacy, devel, unique, rolling..
+    pub status: String,
+}
+
+impl RepoVersion {
+    pub fn is_outdated(&self) -> bool {
+        matches!(self.status.as_str(), "outdated" | "legacy")
+    }
+}
+
+/// Written as `repo:status:version`, versions are the only part that can contain `:`
+impl fmt::Display for RepoVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.repo, self.status, self.version)
+    }
+}
+
+impl FromStr for RepoVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.splitn(3, ':');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(repo), Some(status), Some(version)) => Ok(Self {
+                repo: repo.to_owned(),
+                status: status.to_owned(),
+                version: version.to_owned(),
+            }),
+            _ => Err(format!("Invalid repository version `{}`", s).into()),
+        }
+    }
+}
+
+impl Repology {
+    pub const KIND: &'static str = "repology";
+
+    pub fn from_spec(spec: Spec) -> Result<Self> {
+        spec.check_options(&["url"])?;
+        Ok(Self {
+            url: spec.get("url").map(ToOwned::to_owned),
+            project: spec.target,
+        })
+    }
+
+    pub fn to_spec(&self) -> Spec {
+        Spec::new(Self::KIND, &self.project).option("url", self.url.as_deref())
+    }
+
+    pub fn fetch_latest(&self, deadline: Instant) -> Result<Latest> {
+        let base = self.url.as_deref().unwrap_or(URL).trim_end_matches('/');
+        let mut repos: Vec<RepoVersion> = http::get_json(
+            &format!("{}/api/v1/project/{}", base, self.project),
+            &[],
+            deadline,
+        )?;
+        // A repository can ship several packages built from the same project
+        repos.sort_by(|a, b| {
+            a.repo
+                .cmp(&b.repo)
+                .then(natural_cmp(&b.version, &a.version))
+        });
+        repos.dedup_by(|a, b| a.repo == b.repo && a.version == b.version);
+
+        let value = repos
+            .iter()
+            .find(|repo| matches!(repo.status.as_str(), "newest" | "unique"))
+            .or_else(|| {
+                repos
+                    .iter()
+                    .max_by(|a, b| natural_cmp(&a.version, &b.version))
+            })
+            .map(|repo| repo.version.clone())
+            .ok_or_else(|| format!("Repology doesn't know `{}`", self.project))?;
+        Ok(Latest {
+            repos,
+            ..value.into()
+        })
+    }
+}
+
+impl Fetch for Repology {
+    fn fetch(&self, deadline: Instant) -> Result<String> {
+        Ok(self.fetch_latest(deadline)?.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::provider::tests::serve;
+
+    fn fetch_latest(url: &str) -> Result<Latest> {
+        let repology =
+            Repology::from_spec(Spec::new(Repology::KIND, "ripgrep").option("url", Some(url)))?;
+        repology.fetch_latest(Instant::now() + Duration::from_secs(10))
+    }
+
+    #[test]
+    fn newest_and_deduplicated() {
+        let url = serve(|path| {
+            (path == "/api/v1/project/ripgrep").then(|| {
+                r#"[
+                    {"repo": "debian_12", "version": "13.0.0", "status": "outdated"},
+                    {"repo": "arch", "version": "14.1.0", "status": "newest"},
+                    {"repo": "arch", "version": "14.1.0", "status": "newest"},
+                    {"repo": "nix", "version": "14.1.0", "status": "newest"},
+                    {"repo": "nix", "version": "9.0.0", "status": "legacy"}
+                ]"#
+                .to_owned()
+            })
+        });
+        let latest = fetch_latest(&url).unwrap();
+        assert_eq!(latest.value, "14.1.0");
+        let repos: Vec<_> = latest.repos.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            repos,
+            [
+                "arch:newest:14.1.0",
+                "debian_12:outdated:13.0.0",
+                "nix:newest:14.1.0",
+                "nix:legacy:9.0.0",
+            ]
+        );
+        assert!(latest.repos[1].is_outdated());
+    }
+
+    #[test]
+    fn highest_without_newest() {
+        let url = serve(|_| {
+            Some(
+                r#"[
+                    {"repo": "a", "version": "1.9", "status": "outdated"},
+                    {"repo": "b", "version": "1.10", "status": "outdated"}
+                ]"#
+                .to_owned(),
+            )
+        });
+        assert_eq!(fetch_latest(&url).unwrap().value, "1.10");
+
+        let url = serve(|_| Some("[]".to_owned()));
+        let error = fetch_latest(&url).unwrap_err().to_string();
+        assert_eq!(error, "Repology doesn't know `ripgrep`");
+    }
+
+    #[test]
+    fn repo_version_round_trip() {
+        let repo: RepoVersion = "debian_12:outdated:1:2.3-4".parse().unwrap();
+        assert_eq!(repo.version, "1:2.3-4");
+        assert_eq!(repo.to_string(), "debian_12:outdated:1:2.3-4");
+        assert!("debian_12:outdated".parse::<RepoVersion>().is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::{BTreeSet, HashMap};
+
+use regex::Regex;
+
+use crate::{App, Result};
+
+/// The apps matched by `selectors`, sorted: names, glob patterns like `python-*`, `@tag`
+/// for the apps with that tag, or `--all`
+///
+/// Every selector has to match something, a typo shouldn't quietly shrink the selection
+pub fn select(selectors: &[&str], apps: &HashMap<String, App>) -> Result<Vec<String>> {
+    if selectors.is_empty() {
+        return Err("Expected app names, patterns, @tags or --all".into());
+    }
+    let mut selected = BTreeSet::new();
+    for selector in selectors {
+        let matched: Vec<&String> = if *selector == "--all" {
+            apps.keys().collect()
+        } else if selector.starts_with("--") {
+            return Err(format!("Unknown option `{}`", selector).into());
+        } else if let Some(tag) = selector.strip_prefix('@') {
+            apps.iter()
+                .filter(|(_, app)| app.tags.iter().any(|t| t == tag))
+                .map(|(name, _)| name)
+                .collect()
+        } else if selector.contains(['*', '?']) {
+            let pattern = glob(selector)?;
+            apps.keys().filter(|name| pattern.is_match(name)).collect()
+        } else {
+            apps.get_key_value(*selector)
+                .map(|(name, _)| name)
+                .into_iter()
+                .collect()
+        };
+        if matched.is_empty() && *selector != "--all" {
+            return Err(match selector.contains(['*', '?', '@']) {
+                true => format!("No app matches `{}`", selector),
+                false => format!("App `{}` is not registered.", selector),
+            }
+            .into());
+        }
+        selected.extend(matched.into_iter().cloned());
+    }
+    Ok(selected.into_iter().collect())
+}
+
+/// `*` matches any run of characters and `?` a single one, the rest is literal
+fn glob(pattern: &str) -> Result<Regex> {
+    let regex = regex::escape(pattern)
+        .replace(r"\*", ".*")
+        .replace(r"\?", ".");
+    Ok(Regex::new(&format!("^{}$", regex))?)
+}
+
+/// Tags are single words, so they read well after `@` and in `--tags a,b`
+pub fn parse_tags(s: &str) -> Result<Vec<String>> {
+    s.split(',')
+        .map(|tag| {
+            let tag = tag.trim();
+            if tag.is_empty() || tag.contains(|c: char| c.is_whitespace() || c == '@') {
+                return Err(format!("Invalid tag `{}`", tag).into());
+            }
+            Ok(tag.to_owned())
+        })
+        .collect()
+}
@@ -0,0 +1,81 @@
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::Result;
+
+/// Scripts copied into the data dir, named by the hash of their content so an edit is a
+/// new file and never changes what another app runs
+pub fn dir() -> Result<PathBuf> {
+    Ok(crate::data_dir()?.join("store"))
+}
+
+/// Whether `path` is a copy in the store
+pub fn contains(path: &Path) -> Result<bool> {
+    Ok(path.parent() == Some(dir()?.as_path()))
+}
+
+/// Hex sha256, the name of a script with that content
+pub fn hash(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Copies the script at `path` into the store, returning the copy's path
+///
+/// The copy keeps the extension, editors go by it to pick the syntax
+pub fn add_file(path: &Path) -> Result<PathBuf> {
+    let content = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    add(
+        &content,
+        path.extension().and_then(|extension| extension.to_str()),
+    )
+}
+
+pub fn add(content: &[u8], extension: Option<&str>) -> Result<PathBuf> {
+    let dir = dir()?;
+    let name = match extension {
+        Some(extension) => format!("{}.{}", hash(content), extension),
+        None => hash(content),
+    };
+    let path = dir.join(&name);
+    if !path.exists() {
+        std::fs::create_dir_all(&dir)?;
+        // Never seen half written, the name promises the content
+        let tmp = dir.join(format!(".{}.tmp.{}", name, std::process::id()));
+        std::fs::write(&tmp, content)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o755))?;
+        }
+        std::fs::rename(&tmp, &path)?;
+    }
+    Ok(path)
+}
+
+/// An empty file only we can open, for `ups edit` to work on a copy of a stored script
+pub fn scratch(extension: Option<&str>) -> Result<(PathBuf, File)> {
+    let dir = dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let mut name = format!(".edit.{}", std::process::id());
+    if let Some(extension) = extension {
+        name = format!("{}.{}", name, extension);
+    }
+    let path = dir.join(&name);
+    let mut options = OpenOptions::new();
+    // Fails on anything already there, a symlink included
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options
+        .open(&path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok((path, file))
+}
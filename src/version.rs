@@ -0,0 +1,482 @@
+use std::cmp::Ordering;
+
+/// Compares versions chunk by chunk, numbers numerically and everything else as text,
+/// so that `1.10` sorts after `1.9`
+///
+/// Trailing zero components don't count, `1.0` is the same as `1`
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let is_zero = |chunk: &str| chunk.bytes().all(|c| c == b'0');
+    let (mut a, mut b) = (chunks(a), chunks(b));
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(y)) if is_zero(y) && b.all(is_zero) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(x), None) if is_zero(x) && a.all(is_zero) => return Ordering::Equal,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => {
+                let ordering = match (x.parse::<u128>(), y.parse::<u128>()) {
+                    (Ok(x), Ok(y)) => x.cmp(&y),
+                    _ => x.cmp(y),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+/// `v1.2` -> `1.2`, but `vim-9` stays as is
+fn strip_v(version: &str) -> &str {
+    match version.strip_prefix(['v', 'V']) {
+        Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit()) => rest,
+        _ => version,
+    }
+}
+
+/// Splits into runs of digits and runs of letters, dropping separators
+fn chunks(version: &str) -> impl Iterator<Item = &str> {
+    let mut rest = version;
+    std::iter::from_fn(move || {
+        rest = rest.trim_start_matches(|c: char| !c.is_alphanumeric());
+        let first = rest.chars().next()?;
+        let end = rest
+            .find(|c: char| !c.is_alphanumeric() || c.is_ascii_digit() != first.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+/// Semver pre-releases carry a `-` suffix, build metadata after `+` doesn't count
+pub fn is_semver_prerelease(version: &str) -> bool {
+    version.split('+').next().unwrap_or_default().contains('-')
+}
+
+/// PEP 440 alpha, beta, release candidate and dev releases
+pub fn is_pep440_prerelease(version: &str) -> bool {
+    const PRE: &[&str] = &[
+        "a", "b", "c", "rc", "alpha", "beta", "pre", "preview", "dev",
+    ];
+    let version = version.to_lowercase();
+    let is_prerelease = chunks(&version).any(|chunk| PRE.contains(&chunk));
+    is_prerelease
+}
+
+/// How the versions of an app are ordered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scheme {
+    /// `1.2.3-rc.1+build`, with an optional `v` prefix
+    Semver,
+    /// Python versions, `1.0rc1`, `2!1.0.post1.dev3`
+    Pep440,
+    /// dpkg ordering, `1:2.3~rc1-4`
+    Debian,
+    /// Purely numeric dates, `2023.10.05`, `23.04.1`
+    Calver,
+    /// Numbers compared as numbers and everything else as text, works with anything
+    #[default]
+    Natural,
+}
+
+/// Where the latest value stands relative to another one, usually the snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Same,
+    /// Upstream moved forward
+    Newer,
+    /// Upstream went back, a re-tag or a scraping glitch
+    Older,
+    /// One side is missing or doesn't follow the scheme
+    Incomparable,
+}
+
+impl Scheme {
+    pub const NAMES: &'static [&'static str] = &["semver", "pep440", "debian", "calver", "natural"];
+
+    /// `None` when either version doesn't follow the scheme
+    pub fn compare(self, a: &str, b: &str) -> Option<Ordering> {
+        match self {
+            Self::Semver => Some(Semver::parse(a)?.cmp(&Semver::parse(b)?)),
+            Self::Pep440 => Some(Pep440::parse(a)?.cmp(&Pep440::parse(b)?)),
+            Self::Debian => Some(debian_cmp(a, b)),
+            Self::Calver => Some(calver(a)?.cmp(&calver(b)?)),
+            // Only when comparing two values, tags sorted with `natural_cmp` keep `v1.0`
+            // above `nightly`
+            Self::Natural => Some(natural_cmp(strip_v(a), strip_v(b))),
+        }
+    }
+
+    /// Status of `latest` compared to `reference`
+    pub fn status(self, reference: &str, latest: &str) -> Status {
+        if reference == latest {
+            return Status::Same;
+        }
+        if [reference, latest].contains(&crate::NONE) {
+            return Status::Incomparable;
+        }
+        match self.compare(latest, reference) {
+            Some(Ordering::Greater) => Status::Newer,
+            Some(Ordering::Less) => Status::Older,
+            // Different strings that order the same, like `1.0` and `v1.0`
+            Some(Ordering::Equal) => Status::Same,
+            None => Status::Incomparable,
+        }
+    }
+}
+
+impl std::fmt::Display for Scheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Semver => "semver",
+            Self::Pep440 => "pep440",
+            Self::Debian => "debian",
+            Self::Calver => "calver",
+            Self::Natural => "natural",
+        };
+        f.write_str(name)
+    }
+}
+
+impl std::str::FromStr for Scheme {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s {
+            "semver" => Ok(Self::Semver),
+            "pep440" => Ok(Self::Pep440),
+            "debian" => Ok(Self::Debian),
+            "calver" => Ok(Self::Calver),
+            "natural" => Ok(Self::Natural),
+            _ => Err(format!(
+                "Unknown version scheme `{}`, expected one of: {}",
+                s,
+                Self::NAMES.join(", ")
+            )
+            .into()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Semver<'a> {
+    core: [u64; 3],
+    pre: Vec<&'a str>,
+}
+
+impl<'a> Semver<'a> {
+    /// Lenient about missing minor and patch numbers, tags like `v2.1` are common
+    fn parse(version: &'a str) -> Option<Self> {
+        let version = version.strip_prefix('v').unwrap_or(version);
+        let version = version.split('+').next()?;
+        let (core, pre) = match version.split_once('-') {
+            Some((core, pre)) => (core, pre.split('.').collect()),
+            None => (version, vec![]),
+        };
+        let mut numbers = [0; 3];
+        let mut parts = core.split('.');
+        for (i, part) in parts.by_ref().take(3).enumerate() {
+            numbers[i] = part.parse().ok()?;
+        }
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self { core: numbers, pre })
+    }
+}
+
+impl Ord for Semver<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.core.cmp(&other.core).then_with(|| {
+            // A pre-release comes before the release itself
+            match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => {
+                    for (a, b) in self.pre.iter().zip(&other.pre) {
+                        let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+                            (Ok(a), Ok(b)) => a.cmp(&b),
+                            (Ok(_), Err(_)) => Ordering::Less,
+                            (Err(_), Ok(_)) => Ordering::Greater,
+                            (Err(_), Err(_)) => a.cmp(b),
+                        };
+                        if ordering != Ordering::Equal {
+                            return ordering;
+                        }
+                    }
+                    self.pre.len().cmp(&other.pre.len())
+                }
+            }
+        })
+    }
+}
+
+impl PartialOrd for Semver<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A PEP 440 version reduced to a key that sorts the way the spec says
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Pep440 {
+    epoch: u64,
+    release: Vec<u64>,
+    /// `(0, 0)` for dev releases of the final version, `(1, a|b|rc, n)` for pre-releases,
+    /// `(2, 0, 0)` for final releases
+    pre: (u8, u8, u64),
+    /// `None` sorts first
+    post: Option<u64>,
+    /// `None` sorts last, as `u64::MAX`
+    dev: u64,
+}
+
+impl Pep440 {
+    fn parse(version: &str) -> Option<Self> {
+        let version = version.trim().to_lowercase();
+        let version = version.strip_prefix('v').unwrap_or(&version);
+        // Local versions (`+ubuntu1`) don't take part in ordering here
+        let version = version.split('+').next()?;
+        let (epoch, rest) = match version.split_once('!') {
+            Some((epoch, rest)) => (epoch.parse().ok()?, rest),
+            None => (0, version),
+        };
+
+        let release_end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let mut release: Vec<u64> = rest[..release_end]
+            .trim_end_matches('.')
+            .split('.')
+            .map(|n| n.parse().ok())
+            .collect::<Option<_>>()?;
+        while release.len() > 1 && release.last() == Some(&0) {
+            release.pop();
+        }
+
+        let mut pre = None;
+        let mut post = None;
+        let mut dev = None;
+        let mut rest = &rest[release_end..];
+        while !rest.is_empty() {
+            rest = rest.trim_start_matches(['.', '-', '_']);
+            let label_end = rest
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(rest.len());
+            let (label, tail) = rest.split_at(label_end);
+            let number_end = tail
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(tail.len());
+            let (number, tail) = tail.split_at(number_end);
+            let number = if number.is_empty() {
+                0
+            } else {
+                number.parse().ok()?
+            };
+            match label {
+                "a" | "alpha" if pre.is_none() => pre = Some((0, number)),
+                "b" | "beta" if pre.is_none() => pre = Some((1, number)),
+                "rc" | "c" | "pre" | "preview" if pre.is_none() => pre = Some((2, number)),
+                "post" | "rev" | "r" if post.is_none() => post = Some(number),
+                "dev" if dev.is_none() => dev = Some(number),
+                // `1.0-1` is an implicit post release
+                "" if post.is_none() && number_end != 0 => post = Some(number),
+                _ => return None,
+            }
+            rest = tail;
+        }
+
+        let pre = match (pre, post, dev) {
+            (Some((kind, n)), _, _) => (1, kind, n),
+            (None, None, Some(_)) => (0, 0, 0),
+            _ => (2, 0, 0),
+        };
+        Some(Self {
+            epoch,
+            release,
+            pre,
+            post,
+            dev: dev.unwrap_or(u64::MAX),
+        })
+    }
+}
+
+/// dpkg's algorithm, `epoch:upstream-revision` where `~` sorts before anything, even the end
+fn debian_cmp(a: &str, b: &str) -> Ordering {
+    fn split(version: &str) -> (u64, &str, &str) {
+        let (epoch, rest) = match version.split_once(':') {
+            Some((epoch, rest)) => (epoch.parse().unwrap_or(0), rest),
+            None => (0, version),
+        };
+        let (upstream, revision) = rest.rsplit_once('-').unwrap_or((rest, ""));
+        (epoch, upstream, revision)
+    }
+
+    fn order(c: Option<u8>) -> i32 {
+        match c {
+            Some(b'~') => -1,
+            Some(c) if c.is_ascii_digit() => 0,
+            None => 0,
+            Some(c) if c.is_ascii_alphabetic() => c as i32,
+            Some(c) => c as i32 + 256,
+        }
+    }
+
+    fn verrevcmp(a: &str, b: &str) -> Ordering {
+        let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+        while !a.is_empty() || !b.is_empty() {
+            while a.first().is_some_and(|c| !c.is_ascii_digit())
+                || b.first().is_some_and(|c| !c.is_ascii_digit())
+            {
+                let ordering = order(a.first().copied()).cmp(&order(b.first().copied()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a = a.get(1..).unwrap_or_default();
+                b = b.get(1..).unwrap_or_default();
+            }
+            let digits = |s: &[u8]| s.iter().take_while(|c| c.is_ascii_digit()).count();
+            let (da, db) = (digits(a), digits(b));
+            let number = |s: &[u8]| {
+                std::str::from_utf8(s)
+                    .ok()
+                    .and_then(|s| s.parse::<u128>().ok())
+                    .unwrap_or(0)
+            };
+            let ordering = number(&a[..da]).cmp(&number(&b[..db]));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            a = &a[da..];
+            b = &b[db..];
+        }
+        Ordering::Equal
+    }
+
+    let (a, b) = (split(a), split(b));
+    a.0.cmp(&b.0)
+        .then_with(|| verrevcmp(a.1, b.1))
+        .then_with(|| verrevcmp(a.2, b.2))
+}
+
+/// Numeric components only, anything else isn't a date based version
+fn calver(version: &str) -> Option<Vec<u64>> {
+    let version = version.strip_prefix('v').unwrap_or(version);
+    version
+        .split(['.', '-', '_'])
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each version sorts strictly before the next one
+    fn assert_ascending(scheme: Scheme, versions: &[&str]) {
+        for pair in versions.windows(2) {
+            assert_eq!(
+                scheme.compare(pair[0], pair[1]),
+                Some(Ordering::Less),
+                "{}: {} < {}",
+                scheme,
+                pair[0],
+                pair[1]
+            );
+            assert_eq!(scheme.compare(pair[1], pair[0]), Some(Ordering::Greater));
+        }
+    }
+
+    #[test]
+    fn pep440() {
+        assert_ascending(
+            Scheme::Pep440,
+            &["1.0.dev1", "1.0a1", "1.0rc1", "1.0", "1.0.post1"],
+        );
+        assert_ascending(Scheme::Pep440, &["1.0", "1!0.1"]);
+        assert_eq!(
+            Scheme::Pep440.compare("1.0", "1.0.0"),
+            Some(Ordering::Equal)
+        );
+        assert!(is_pep440_prerelease("1.0rc1"));
+        assert!(!is_pep440_prerelease("1.0.post1"));
+    }
+
+    #[test]
+    fn debian() {
+        assert_ascending(
+            Scheme::Debian,
+            &["1.0~rc1", "1.0", "1.0-1", "1.0+b1", "1:0.1"],
+        );
+    }
+
+    #[test]
+    fn semver() {
+        assert_ascending(
+            Scheme::Semver,
+            &[
+                "1.0.0-alpha",
+                "1.0.0-alpha.1",
+                "1.0.0-beta",
+                "1.0.0",
+                "v1.0.1",
+            ],
+        );
+        assert_eq!(
+            Scheme::Semver.compare("1.0", "1.0.0+build"),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(Scheme::Semver.compare("1.0.0.0", "1.0.0"), None);
+        assert!(is_semver_prerelease("1.0.0-rc.1+build"));
+        assert!(!is_semver_prerelease("1.0.0+build-1"));
+    }
+
+    #[test]
+    fn natural_and_calver() {
+        assert_ascending(Scheme::Natural, &["1.9", "1.10", "1.10.1"]);
+        assert_ascending(Scheme::Calver, &["2023.9.1", "2023.10.05", "2024.01"]);
+        assert_eq!(Scheme::Calver.compare("2023.10", "2023.10-beta"), None);
+    }
+
+    #[test]
+    fn natural_ignores_formatting() {
+        for (a, b) in [
+            ("1.0", "v1.0"),
+            ("1.0", "1.0.0"),
+            ("V2", "2.0"),
+            ("1_0", "1.0"),
+        ] {
+            assert_eq!(
+                Scheme::Natural.compare(a, b),
+                Some(Ordering::Equal),
+                "{} == {}",
+                a,
+                b
+            );
+            assert_eq!(Scheme::Natural.status(a, b), Status::Same);
+        }
+        assert_eq!(Scheme::Natural.status("1.0", "v1.0.1"), Status::Newer);
+        assert_eq!(Scheme::Natural.status("v1.1", "1.0"), Status::Older);
+        assert_eq!(
+            Scheme::Natural.compare("vim-9", "9"),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(natural_cmp("1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(natural_cmp("1.0", "1.0.rc1"), Ordering::Less);
+    }
+
+    #[test]
+    fn status() {
+        assert_eq!(Scheme::Natural.status("1.9", "1.10"), Status::Newer);
+        assert_eq!(Scheme::Natural.status("1.10", "1.9"), Status::Older);
+        assert_eq!(Scheme::Semver.status("1.0", "v1.0"), Status::Same);
+        assert_eq!(Scheme::Semver.status("1.0", "latest"), Status::Incomparable);
+        assert_eq!(
+            Scheme::Natural.status(crate::NONE, "1.0"),
+            Status::Incomparable
+        );
+    }
+}